@@ -0,0 +1,621 @@
+//! Creatures from Bert Chan's Lenia animal catalogue (`animals.json`).
+//!
+//! Single channel animals look like
+//! `{"code": "O2u", "name": "Orbium unicaudatus", "params": {"R": 13, "T": 10, "b": "1", "m": 0.15, "s": 0.015, "kn": 1, "gn": 1}, "cells": "7.MD6.qL$..."}`,
+//! multi channel ones have a list of `kernels` in `params` and one cell string per channel.
+//!
+//! Cells are run length encoded row by row: `.` is an empty cell, `A`..`X` are 1..24 and `pA`..`yO` are 25..255,
+//! all out of 255. A number before a cell repeats it, `$` ends a row (a number before it adds empty rows) and `!` ends the pattern.
+
+use anyhow::anyhow;
+
+use crate::{fft_compute::{BoundaryMode, GrowthFunction, UpdateRule}, kernel_description::{KernelCore, KernelDescription}, simulation::{ChannelKernel, SimulationConfig}};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animal {
+    pub code: String,
+    pub name: String,
+    pub config: SimulationConfig,
+    pub width: u32,
+    pub height: u32,
+    /// `config.channels` planes of width x height cells
+    pub cells: Vec<f32>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AnimalJson {
+    code: String,
+    name: String,
+    params: ParamsJson,
+    cells: CellsJson,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum CellsJson {
+    Single(String),
+    Channels(Vec<String>),
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ParamsJson {
+    /// the catalogue writes these as integers, but not always
+    #[serde(rename = "R")]
+    radius: serde_json::Number,
+    #[serde(rename = "T")]
+    time_step: serde_json::Number,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    b: Option<ShellsJson>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    m: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    s: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kn: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gn: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kernels: Option<Vec<KernelJson>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct KernelJson {
+    b: ShellsJson,
+    m: f32,
+    s: f32,
+    /// weight
+    h: f32,
+    /// radius relative to R
+    r: f32,
+    c0: u32,
+    c1: u32,
+}
+
+/// `"1,5/12,2/3"` in the catalogue, some exports use plain numbers
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum ShellsJson {
+    Text(String),
+    Values(Vec<f32>),
+}
+
+impl ShellsJson {
+    fn parse(&self) -> anyhow::Result<Vec<f32>> {
+        match self {
+            Self::Values(values) => Ok(values.clone()),
+            Self::Text(text) => text.split(',').map(|b| parse_fraction(b.trim())).collect(),
+        }
+    }
+}
+
+fn parse_fraction(text: &str) -> anyhow::Result<f32> {
+    let value = match text.split_once('/') {
+        Some((n, d)) => n.trim().parse::<f32>()? / d.trim().parse::<f32>()?,
+        None => text.parse::<f32>()?,
+    };
+
+    Ok(value)
+}
+
+/// writes `value` as a small fraction when it is one, like the catalogue does
+fn format_fraction(value: f32) -> String {
+    for d in 1..=12 {
+        let n = (value * d as f32).round();
+        if (n / d as f32 - value).abs() < 1e-5 {
+            return if d == 1 { format!("{}", n) } else { format!("{}/{}", n, d) };
+        }
+    }
+
+    format!("{}", value)
+}
+
+/// whole time steps stay integers, the catalogue writes T = 10 and not 10.0
+fn format_time_step(time_step: f32) -> serde_json::Number {
+    if time_step.fract() == 0.0 {
+        (time_step as u64).into()
+    } else {
+        serde_json::Number::from_f64(time_step as f64).unwrap_or_else(|| 1.into())
+    }
+}
+
+fn format_shells(shells: &[f32]) -> ShellsJson {
+    ShellsJson::Text(shells.iter().map(|b| format_fraction(*b)).collect::<Vec<_>>().join(","))
+}
+
+/// catalogue ids are 1 based, `kn` 4 (staircase) has no equivalent
+fn kernel_core_from_id(id: u32) -> anyhow::Result<KernelCore> {
+    match id {
+        1 => Ok(KernelCore::Polynomial),
+        2 => Ok(KernelCore::Exponential),
+        3 => Ok(KernelCore::Rectangular),
+        _ => Err(anyhow!("unsupported kernel core kn = {}", id)),
+    }
+}
+
+/// the gaussian core has no id of its own and is written as the exponential bump, the closest of the catalogue cores
+fn kernel_core_id(core: KernelCore) -> u32 {
+    match core {
+        KernelCore::Polynomial => 1,
+        KernelCore::Exponential | KernelCore::Gaussian => 2,
+        KernelCore::Rectangular => 3,
+    }
+}
+
+fn growth_function_from_id(id: u32) -> anyhow::Result<GrowthFunction> {
+    match id {
+        1 => Ok(GrowthFunction::Polynomial),
+        2 => Ok(GrowthFunction::Gaussian),
+        3 => Ok(GrowthFunction::Rectangular),
+        _ => Err(anyhow!("unsupported growth function gn = {}", id)),
+    }
+}
+
+fn growth_function_id(growth_function: GrowthFunction) -> anyhow::Result<u32> {
+    match growth_function {
+        GrowthFunction::Polynomial => Ok(1),
+        GrowthFunction::Gaussian => Ok(2),
+        GrowthFunction::Rectangular => Ok(3),
+        GrowthFunction::Life => Err(anyhow!("the life growth function has no equivalent in the animal format")),
+    }
+}
+
+/// decodes one channel into rows of cells in [0, 1], rows can have different lengths
+pub fn decode_rle(rle: &str) -> anyhow::Result<Vec<Vec<f32>>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut count = String::new();
+    let mut prefix = None;
+
+    for ch in rle.trim().trim_end_matches('!').chars().chain(Some('$')) {
+        if ch.is_whitespace() {
+            continue;
+        }
+
+        let repeat = if count.is_empty() { 1 } else { count.parse::<usize>()? };
+
+        let value = match (prefix, ch) {
+            (None, '0'..='9') => {
+                count.push(ch);
+                continue;
+            }
+            (None, 'p'..='y') => {
+                prefix = Some(ch);
+                continue;
+            }
+            (None, '$') => {
+                rows.push(std::mem::take(&mut row));
+                rows.extend(std::iter::repeat_n(vec![], repeat - 1));
+                count.clear();
+                continue;
+            }
+            (None, '.' | 'b') => 0,
+            (None, 'o') => 255,
+            (None, 'A'..='X') => ch as u32 - 'A' as u32 + 1,
+            (Some(p), 'A'..='X') => (p as u32 - 'p' as u32) * 24 + (ch as u32 - 'A' as u32) + 25,
+            _ => return Err(anyhow!("unexpected '{}' in cells", ch)),
+        };
+
+        row.extend(std::iter::repeat_n(value.min(255) as f32 / 255.0, repeat));
+        count.clear();
+        prefix = None;
+    }
+
+    Ok(rows)
+}
+
+/// encodes one width x height channel, trailing empty cells and rows are dropped
+pub fn encode_rle(cells: &[f32], width: u32) -> String {
+    let mut rle = String::new();
+    let mut empty_rows = 0;
+
+    let token = |v: u32| match v {
+        0 => ".".to_string(),
+        1..=24 => char::from(b'A' + (v - 1) as u8).to_string(),
+        _ => format!("{}{}", char::from(b'p' + ((v - 25) / 24) as u8), char::from(b'A' + ((v - 25) % 24) as u8)),
+    };
+
+    for row in cells.chunks_exact(width as usize) {
+        let values = row.iter().map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u32).collect::<Vec<_>>();
+        let len = values.iter().rposition(|v| *v != 0).map_or(0, |i| i + 1);
+
+        if len == 0 {
+            empty_rows += 1;
+            continue;
+        }
+
+        if !rle.is_empty() || empty_rows > 0 {
+            // one `$` ends the previous row, the rest add empty rows
+            let rows = empty_rows + usize::from(!rle.is_empty());
+            if rows > 1 {
+                rle.push_str(&rows.to_string());
+            }
+            rle.push('$');
+        }
+        empty_rows = 0;
+
+        let mut i = 0;
+        while i < len {
+            let run = values[i..len].iter().take_while(|v| **v == values[i]).count();
+            if run > 1 {
+                rle.push_str(&run.to_string());
+            }
+            rle.push_str(&token(values[i]));
+            i += run;
+        }
+    }
+
+    rle.push('!');
+    rle
+}
+
+impl Animal {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Self::from_value(serde_json::from_str(json)?)
+    }
+
+    /// every animal in a catalogue file, the section headers without cells are skipped
+    pub fn library_from_json(json: &str) -> anyhow::Result<Vec<Self>> {
+        let entries: Vec<serde_json::Value> = serde_json::from_str(json)?;
+
+        entries
+            .into_iter()
+            .filter(|entry| entry.get("cells").is_some())
+            .map(|entry| Self::from_value(serde_json::from_value(entry)?))
+            .collect()
+    }
+
+    fn from_value(animal: AnimalJson) -> anyhow::Result<Self> {
+        let params = animal.params;
+        let radius = params.radius.as_f64().ok_or_else(|| anyhow!("invalid radius R"))? as f32;
+        let time_step = params.time_step.as_f64().ok_or_else(|| anyhow!("invalid time step T"))? as f32;
+
+        let core = params.kn.map(kernel_core_from_id).transpose()?;
+        let growth_function = params.gn.map(growth_function_from_id).transpose()?;
+
+        let kernels = match &params.kernels {
+            Some(kernels) => kernels
+                .iter()
+                .map(|k| Ok(ChannelKernel {
+                    kernel: KernelDescription {
+                        radius: (radius * k.r).round().max(1.0) as u32,
+                        shells: k.b.parse()?,
+                        core: core.unwrap_or(KernelCore::Exponential),
+                        width: 1.0,
+                    },
+                    source: k.c0,
+                    target: k.c1,
+                    m: k.m,
+                    s: k.s,
+                    weight: k.h,
+                }))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            None => vec![ChannelKernel {
+                kernel: KernelDescription {
+                    radius: radius.round().max(1.0) as u32,
+                    shells: params.b.as_ref().ok_or_else(|| anyhow!("animal has no kernel shells (b)"))?.parse()?,
+                    core: core.unwrap_or(KernelCore::Polynomial),
+                    width: 1.0,
+                },
+                m: params.m.ok_or_else(|| anyhow!("animal has no growth center (m)"))?,
+                s: params.s.ok_or_else(|| anyhow!("animal has no growth width (s)"))?,
+                ..Default::default()
+            }],
+        };
+
+        let channel_cells = match animal.cells {
+            CellsJson::Single(cells) => vec![decode_rle(&cells)?],
+            CellsJson::Channels(channels) => channels.iter().map(|c| decode_rle(c)).collect::<anyhow::Result<Vec<_>>>()?,
+        };
+
+        let width = channel_cells.iter().flatten().map(Vec::len).max().unwrap_or(0) as u32;
+        let height = channel_cells.iter().map(Vec::len).max().unwrap_or(0) as u32;
+
+        // pad every row and channel to the bounding box
+        let mut cells = vec![0f32; (width * height) as usize * channel_cells.len()];
+        for (c, rows) in channel_cells.iter().enumerate() {
+            for (y, row) in rows.iter().enumerate() {
+                let start = c * (width * height) as usize + y * width as usize;
+                cells[start..start + row.len()].copy_from_slice(row);
+            }
+        }
+
+        // an empty pattern like `!` decodes to a single empty row
+        if !cells.iter().any(|c| *c > 0.0) {
+            return Err(anyhow!("animal has no living cells"));
+        }
+
+        let config = SimulationConfig {
+            channels: channel_cells.len() as u32,
+            time_step,
+            growth_function: growth_function.unwrap_or(GrowthFunction::Gaussian),
+            boundary: BoundaryMode::Torus,
+            update_rule: UpdateRule::Euler,
+            kernels,
+        };
+        config.validate()?;
+
+        Ok(Self {
+            code: animal.code,
+            name: animal.name,
+            config,
+            width,
+            height,
+            cells,
+        })
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        let config = &self.config;
+        let radius = config.max_radius();
+
+        let params = match config.kernels.as_slice() {
+            [kernel] if config.channels == 1 => ParamsJson {
+                radius: radius.into(),
+                time_step: format_time_step(config.time_step),
+                b: Some(format_shells(&kernel.kernel.shells)),
+                m: Some(kernel.m),
+                s: Some(kernel.s),
+                kn: Some(kernel_core_id(kernel.kernel.core)),
+                gn: Some(growth_function_id(config.growth_function)?),
+                kernels: None,
+            },
+            kernels => {
+                let core = kernels[0].kernel.core;
+                if kernels.iter().any(|k| k.kernel.core != core) {
+                    return Err(anyhow!("every kernel needs the same core to be written as an animal"));
+                }
+
+                ParamsJson {
+                    radius: radius.into(),
+                    time_step: format_time_step(config.time_step),
+                    b: None,
+                    m: None,
+                    s: None,
+                    kn: Some(kernel_core_id(core)),
+                    gn: Some(growth_function_id(config.growth_function)?),
+                    kernels: Some(kernels
+                        .iter()
+                        .map(|k| KernelJson {
+                            b: format_shells(&k.kernel.shells),
+                            m: k.m,
+                            s: k.s,
+                            h: k.weight,
+                            r: k.kernel.radius as f32 / radius as f32,
+                            c0: k.source,
+                            c1: k.target,
+                        })
+                        .collect()),
+                }
+            }
+        };
+
+        let planes = self.cells
+            .chunks_exact((self.width * self.height) as usize)
+            .map(|plane| encode_rle(plane, self.width))
+            .collect::<Vec<_>>();
+
+        let cells = match <[String; 1]>::try_from(planes) {
+            Ok([cells]) => CellsJson::Single(cells),
+            Err(planes) => CellsJson::Channels(planes),
+        };
+
+        Ok(serde_json::to_string(&AnimalJson {
+            code: self.code.clone(),
+            name: self.name.clone(),
+            params,
+            cells,
+        })?)
+    }
+
+    /// the rows `stamp` writes into a width x height world as (channel, x, y, cells), centered on (x, y) and wrapping
+    /// around the edges. Rows crossing the right edge are split in two, so every run is contiguous in the grid.
+    pub fn stamp_runs(&self, width: u32, height: u32, x: u32, y: u32) -> Vec<(u32, u32, u32, &[f32])> {
+        let plane_size = (self.width * self.height) as usize;
+        if plane_size == 0 || width == 0 || height == 0 {
+            return vec![];
+        }
+
+        let stamp_width = self.width.min(width);
+        let stamp_height = self.height.min(height);
+
+        let left = (x % width + width - stamp_width / 2) % width;
+        let top = (y % height + height - stamp_height / 2) % height;
+        let first = stamp_width.min(width - left) as usize;
+
+        let mut runs = vec![];
+        for (channel, plane) in self.cells.chunks_exact(plane_size).enumerate() {
+            for row in 0..stamp_height {
+                let grid_y = (top + row) % height;
+                let cells = &plane[(row * self.width) as usize..][..stamp_width as usize];
+
+                runs.push((channel as u32, left, grid_y, &cells[..first]));
+                if first < cells.len() {
+                    runs.push((channel as u32, 0, grid_y, &cells[first..]));
+                }
+            }
+        }
+
+        runs
+    }
+
+    /// writes the animal into a channels x height x width grid, centered on (x, y) and wrapping around the edges
+    pub fn stamp(&self, grid: &mut [f32], width: u32, height: u32, x: u32, y: u32) {
+        for (channel, grid_x, grid_y, cells) in self.stamp_runs(width, height, x, y) {
+            let start = ((channel * height + grid_y) * width + grid_x) as usize;
+            if let Some(target) = grid.get_mut(start..start + cells.len()) {
+                target.copy_from_slice(cells);
+            }
+        }
+    }
+
+    /// the smallest animal holding every living cell of a channels x height x width grid
+    pub fn from_grid(code: String, name: String, config: SimulationConfig, width: u32, height: u32, grid: &[f32]) -> anyhow::Result<Self> {
+        let plane_size = (width * height) as usize;
+        let alive = |x: u32, y: u32| grid
+            .chunks_exact(plane_size)
+            .any(|plane| plane[(y * width + x) as usize] > 0.0);
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+        for y in 0..height {
+            for x in 0..width {
+                if alive(x, y) {
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x + 1);
+                    max_y = max_y.max(y + 1);
+                }
+            }
+        }
+
+        if max_x == 0 {
+            return Err(anyhow!("there are no living cells to export"));
+        }
+
+        let crop_width = max_x - min_x;
+        let crop_height = max_y - min_y;
+
+        let mut cells = Vec::with_capacity((crop_width * crop_height * config.channels) as usize);
+        for plane in grid.chunks_exact(plane_size) {
+            for y in min_y..max_y {
+                let start = (y * width + min_x) as usize;
+                cells.extend_from_slice(&plane[start..start + crop_width as usize]);
+            }
+        }
+
+        Ok(Self {
+            code,
+            name,
+            config,
+            width: crop_width,
+            height: crop_height,
+            cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORBIUM: &str = r#"{"code": "O2u", "name": "Orbium unicaudatus", "params": {"R": 13, "T": 10, "b": "1", "m": 0.15, "s": 0.015, "kn": 1, "gn": 1},
+        "cells": "7.MD6.qL$6.pKqEqFURpApBRAqQ$5.VqTrSsBrOpXpWpTpWpUpCSCHQ$4.CQrQsTsWsApITNPOHRXqCpU$3.IqDrWsOsTqT3.EWpCqKrBqLP$.DpTqCqLqIqMqR4.pErCrTsSsLqW$.NpWqCqEpVpIpF4.rJsFtJtEsMrE$.qDqXpKpOpLpRF4.ErPtMuAtMsOqB$.pKqLqCpPpVpRqI4.HpMsCtQtItHqTpB$.TpXqGqKrArMpQ4.EpJqWsCsVrNpNE$.SpOqFrCrQsQrQpX3.TqBqXrQrKpXG$.EqBqMrGsArUsJrLqNqJqCqSqUqNpOB$2.pDqLrKsBsGsDrSrGrArCqVqApB$3.pEqHrBrMrUrRrJqTqJpNT$4.pApWqJqQrBqVqJpUV$6.TpNpQpLpIpA!"}"#;
+
+    /// decodes back to width x height, the rows come back without their trailing empty cells
+    fn decode_to_grid(rle: &str, width: u32, height: u32) -> Vec<f32> {
+        let mut grid = vec![0f32; (width * height) as usize];
+        for (y, row) in decode_rle(rle).unwrap().iter().enumerate() {
+            grid[y * width as usize..][..row.len()].copy_from_slice(row);
+        }
+        grid
+    }
+
+    #[test]
+    fn rle_round_trip() {
+        let (width, height) = (9, 6);
+        // empty rows at the start, in the middle and at the end, runs and values on both sides of 24
+        let cells = (0..width * height)
+            .map(|i| match (i / width, i % width) {
+                (0, _) | (3, _) | (5, _) => 0,
+                (_, x) if x < 3 => 255,
+                (y, x) => (y * 37 + x * 11) % 256,
+            } as f32 / 255.0)
+            .collect::<Vec<_>>();
+
+        let rle = encode_rle(&cells, width);
+        assert_eq!(decode_to_grid(&rle, width, height), cells, "{}", rle);
+    }
+
+    #[test]
+    fn empty_rle_round_trip() {
+        let rle = encode_rle(&[0.0; 12], 4);
+        assert_eq!(rle, "!");
+        assert!(decode_rle(&rle).unwrap().iter().flatten().all(|c| *c == 0.0));
+    }
+
+    #[test]
+    fn empty_animals_are_rejected() {
+        for cells in [r#""!""#, r#""""#, r#""3.$2.!""#, r#"[]"#] {
+            let json = ORBIUM.replace(&ORBIUM[ORBIUM.find(r#""cells""#).unwrap()..ORBIUM.len() - 1], &format!(r#""cells": {}"#, cells));
+            assert!(Animal::from_json(&json).is_err(), "{} was accepted", cells);
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let animal = Animal::from_json(ORBIUM).unwrap();
+        assert_eq!(animal.cells.len(), (animal.width * animal.height) as usize);
+
+        let written = Animal::from_json(&animal.to_json().unwrap()).unwrap();
+        assert_eq!(written, animal);
+    }
+
+    #[test]
+    fn from_grid_crops_to_the_living_cells() {
+        let animal = Animal::from_json(ORBIUM).unwrap();
+        let (width, height) = (64, 48);
+
+        let mut grid = vec![0f32; (width * height) as usize];
+        animal.stamp(&mut grid, width, height, 30, 20);
+
+        let cropped = Animal::from_grid(animal.code.clone(), animal.name.clone(), animal.config.clone(), width, height, &grid).unwrap();
+        assert!(cropped.width <= animal.width && cropped.height <= animal.height);
+        assert_eq!(cropped.cells.iter().sum::<f32>(), animal.cells.iter().sum::<f32>());
+
+        // the crop is already tight
+        let mut grid = vec![0f32; (width * height) as usize];
+        cropped.stamp(&mut grid, width, height, 10, 40);
+        let again = Animal::from_grid(animal.code.clone(), animal.name.clone(), animal.config.clone(), width, height, &grid).unwrap();
+        assert_eq!(again, cropped);
+    }
+
+    #[test]
+    fn default_config_can_be_exported() {
+        let mut grid = vec![0f32; 16 * 16];
+        grid[5 * 16 + 7] = 128.0 / 255.0;
+
+        let animal = Animal::from_grid("x".into(), "x".into(), SimulationConfig::default(), 16, 16, &grid).unwrap();
+        let written = Animal::from_json(&animal.to_json().unwrap()).unwrap();
+        assert_eq!(written.config.kernels[0].kernel.core, KernelCore::Exponential);
+        assert_eq!(written.cells, animal.cells);
+    }
+
+    #[test]
+    fn stamp_wraps_around_the_edges() {
+        let config = SimulationConfig { channels: 2, ..Default::default() };
+        let (width, height) = (5, 4);
+        let animal = Animal {
+            code: "x".into(),
+            name: "x".into(),
+            config,
+            width: 3,
+            height: 2,
+            cells: (1..=12).map(|i| i as f32).collect(),
+        };
+
+        let mut grid = vec![0f32; 2 * 5 * 4];
+        animal.stamp(&mut grid, width, height, 0, 0);
+
+        // centered on the corner, the left column and the top row land on the far edges
+        for channel in 0..2 {
+            for row in 0..2 {
+                for column in 0..3 {
+                    let x = (column + width - 1) % width;
+                    let y = (row + height - 1) % height;
+                    let value = grid[((channel * height + y) * width + x) as usize];
+                    assert_eq!(value, animal.cells[((channel * 2 + row) * 3 + column) as usize]);
+                }
+            }
+        }
+        assert_eq!(grid.iter().filter(|c| **c != 0.0).count(), 12);
+
+        let empty = Animal { width: 0, height: 0, cells: vec![], ..animal };
+        empty.stamp(&mut grid, width, height, 2, 2);
+        assert!(empty.stamp_runs(width, height, 2, 2).is_empty());
+    }
+
+    #[test]
+    fn empty_grids_are_not_exported() {
+        let grid = vec![0f32; 16 * 16];
+        assert!(Animal::from_grid("x".into(), "x".into(), SimulationConfig::default(), 16, 16, &grid).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::anyhow;
+
+use crate::{storage_manager::Storage, uniforms_manager::Uniforms};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum BrushShape {
+    #[default]
+    Circle = 0,
+    Square = 1,
+    /// bell shaped, `size` is twice its standard deviation
+    Gaussian = 2,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum BrushFill {
+    /// uniform noise up to `value`
+    #[default]
+    Random = 0,
+    Constant = 1,
+    Eraser = 2,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum BrushBlend {
+    /// fades from the old cells to the fill with the brush's coverage
+    #[default]
+    Replace = 0,
+    /// adds the fill times the coverage, the eraser subtracts
+    Add = 1,
+}
+
+/// How the brush paints, see brush.wgsl
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Brush {
+    pub shape: BrushShape,
+    pub fill: BrushFill,
+    pub blend: BrushBlend,
+    /// radius in cells, half the side for squares
+    pub size: f32,
+    /// constant fill, or the scale of the noise
+    pub value: f32,
+    /// fraction of the radius the edge fades over, 0 is a hard edge
+    #[serde(default)]
+    pub softness: f32,
+    /// quarter turns clockwise applied to pasted patterns
+    #[serde(default)]
+    pub rotation: u32,
+    /// flips pasted patterns horizontally, before the rotation
+    #[serde(default)]
+    pub mirror: bool,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            shape: BrushShape::Square,
+            fill: BrushFill::Random,
+            blend: BrushBlend::Replace,
+            size: 10.0,
+            value: 0.5,
+            softness: 0.0,
+            rotation: 0,
+            mirror: false,
+        }
+    }
+}
+
+impl Brush {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(self.size.is_finite() && self.size >= 0.0) {
+            return Err(anyhow!("brush size has to be a positive number"));
+        }
+
+        if !(0.0..=1.0).contains(&self.softness) {
+            return Err(anyhow!("brush softness has to be between 0 and 1"));
+        }
+
+        Ok(())
+    }
+}
+
+/// A pattern to paste with the brush, `channels` planes of width x height cells
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub cells: Vec<f32>,
+}
+
+pub struct BrushState {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pattern: Storage,
+    pub uniforms: Uniforms<BrushUniforms>,
+}
+
+#[derive(Copy, Clone, Debug, Default, encase::ShaderType)]
+pub struct BrushUniforms {
+    pub height: u32,
+    pub width: u32,
+    pub channels: u32,
+    pub x: u32,
+    pub y: u32,
+    pub size: f32,
+    pub shape: u32,
+    pub fill: u32,
+    pub blend: u32,
+    pub value: f32,
+    pub softness: f32,
+    pub seed: u32,
+    pub use_pattern: u32,
+    pub pattern_width: u32,
+    pub pattern_height: u32,
+    pub pattern_channels: u32,
+    pub rotation: u32,
+    pub mirror: u32,
+}
+
+impl BrushState {
+    fn create_bind_group(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        grid: &Storage,
+        pattern: &Storage,
+        uniforms: &Uniforms<BrushUniforms>
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Brush Bind Group"),
+            layout: bind_group_layout,
+            entries: &[
+                uniforms.bind_group_entry(0),
+                grid.bind_group_entry(1),
+                pattern.bind_group_entry(2),
+            ]
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Storage,
+        uniforms: Uniforms<BrushUniforms>,
+    ) -> Self {
+        // bindings can't be empty, a single dead cell stands in until a pattern is loaded
+        let pattern = Storage::new(device, "Brush Pattern", &[0f32]);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Brush Bind Group Layout"),
+            entries: &[
+                uniforms.layout_entry(0, wgpu::ShaderStages::COMPUTE),
+                grid.layout_entry(1, wgpu::ShaderStages::COMPUTE, false),
+                pattern.layout_entry(2, wgpu::ShaderStages::COMPUTE, true),
+            ],
+        });
+
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, grid, &pattern, &uniforms);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("brush.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!("random.wgsl"), include_str!("brush.wgsl")).into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Brush Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Brush Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: None,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            pattern,
+            uniforms,
+        }
+    }
+
+    pub fn recreate_bind_groups(
+        &mut self,
+        device: &wgpu::Device,
+        grid: &Storage,
+    ) {
+       self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, grid, &self.pattern, &self.uniforms)
+    }
+
+    pub fn set_brush(&mut self, brush: &Brush) {
+        self.uniforms.shape = brush.shape as u32;
+        self.uniforms.fill = brush.fill as u32;
+        self.uniforms.blend = brush.blend as u32;
+        self.uniforms.size = brush.size;
+        self.uniforms.value = brush.value;
+        self.uniforms.softness = brush.softness;
+        self.uniforms.rotation = brush.rotation % 4;
+        self.uniforms.mirror = brush.mirror as u32;
+    }
+
+    /// uploads the pattern pasted by `paste`, `grid` is needed to rebind the new buffer
+    pub fn set_pattern(
+        &mut self,
+        device: &wgpu::Device,
+        grid: &Storage,
+        pattern: &Pattern,
+    ) -> anyhow::Result<()> {
+        if pattern.width == 0 || pattern.height == 0 || pattern.channels == 0 {
+            return Err(anyhow!("pattern cannot be empty"));
+        }
+
+        if pattern.cells.len() != (pattern.width * pattern.height * pattern.channels) as usize {
+            return Err(anyhow!(
+                "expected {} cells for {} channels of {}x{}, got {}",
+                pattern.width * pattern.height * pattern.channels, pattern.channels, pattern.width, pattern.height, pattern.cells.len()
+            ));
+        }
+
+        self.pattern = Storage::new(device, "Brush Pattern", &pattern.cells);
+        self.uniforms.pattern_width = pattern.width;
+        self.uniforms.pattern_height = pattern.height;
+        self.uniforms.pattern_channels = pattern.channels;
+
+        self.recreate_bind_groups(device, grid);
+
+        Ok(())
+    }
+
+    pub fn has_pattern(&self) -> bool {
+        self.uniforms.pattern_width > 0
+    }
+
+    /// paints the brush centered on the cell (x, y)
+    pub fn paint(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        x: u32,
+        y: u32,
+    ) {
+        self.uniforms.use_pattern = 0;
+        self.run(encoder, queue, x, y);
+    }
+
+    /// pastes the loaded pattern centered on the cell (x, y), with the brush's rotation, mirroring and blend mode
+    pub fn paste(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        x: u32,
+        y: u32,
+    ) {
+        if !self.has_pattern() {
+            log::warn!("there is no pattern to paste");
+            return;
+        }
+
+        self.uniforms.use_pattern = 1;
+        self.run(encoder, queue, x, y);
+    }
+
+    fn run(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        x: u32,
+        y: u32,
+    ) {
+        self.uniforms.x = x;
+        self.uniforms.y = y;
+        self.uniforms.write(queue);
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Brush Compute Pass"),
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+
+        let workgroups_x = self.uniforms.width.div_ceil(16);
+        let workgroups_y = self.uniforms.height.div_ceil(16);
+        pass.dispatch_workgroups(workgroups_x, workgroups_y, self.uniforms.channels);
+    }
+}
@@ -0,0 +1,154 @@
+// cell_random comes from random.wgsl
+
+struct BrushUniforms {
+    height: u32,
+    width: u32,
+    channels: u32,
+    x: u32,
+    y: u32,
+    size: f32,
+    shape: u32,
+    fill: u32,
+    blend: u32,
+    value: f32,
+    softness: f32,
+    seed: u32,
+    use_pattern: u32,
+    pattern_width: u32,
+    pattern_height: u32,
+    pattern_channels: u32,
+    rotation: u32,
+    mirror: u32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: BrushUniforms;
+@group(0) @binding(1) var<storage, read_write> output: array<f32>;
+// pattern_channels planes of pattern_width x pattern_height cells
+@group(0) @binding(2) var<storage, read> pattern: array<f32>;
+
+const SHAPE_CIRCLE: u32 = 0u;
+const SHAPE_SQUARE: u32 = 1u;
+const SHAPE_GAUSSIAN: u32 = 2u;
+
+const FILL_RANDOM: u32 = 0u;
+const FILL_CONSTANT: u32 = 1u;
+const FILL_ERASER: u32 = 2u;
+
+const BLEND_REPLACE: u32 = 0u;
+const BLEND_ADD: u32 = 1u;
+
+// shortest offset from a to b on a ring of length len
+fn wrapped_offset(a: u32, b: u32, len: u32) -> i32 {
+    let d = (i32(b) - i32(a) + i32(len)) % i32(len);
+    return select(d, d - i32(len), d > i32(len) / 2);
+}
+
+// how strongly the brush covers a cell at offset d from its center, 0 outside of it
+fn coverage(d: vec2<f32>) -> f32 {
+    let size = max(uniforms.size, 0.5);
+
+    switch uniforms.shape {
+        case SHAPE_SQUARE: {
+            let r = max(abs(d.x), abs(d.y)) / size;
+            return select(0.0, 1.0 - smoothstep(1.0 - uniforms.softness, 1.0, r), r <= 1.0);
+        }
+        case SHAPE_GAUSSIAN: {
+            // sigma is half the size, cut off at 3 sigma
+            let r = length(d) / size;
+            return select(0.0, exp(-2.0 * r * r), r <= 1.5);
+        }
+        default: {
+            let r = length(d) / size;
+            return select(0.0, 1.0 - smoothstep(1.0 - uniforms.softness, 1.0, r), r <= 1.0);
+        }
+    }
+}
+
+// pattern cell landing on the offset d from the top left corner of the rotated pattern
+fn pattern_cell(d: vec2<u32>, channel: u32) -> f32 {
+    let w = uniforms.pattern_width;
+    let h = uniforms.pattern_height;
+
+    // quarter turns clockwise
+    var p: vec2<u32>;
+    switch uniforms.rotation {
+        case 1u: { p = vec2<u32>(d.y, h - 1u - d.x); }
+        case 2u: { p = vec2<u32>(w - 1u - d.x, h - 1u - d.y); }
+        case 3u: { p = vec2<u32>(w - 1u - d.y, d.x); }
+        default: { p = d; }
+    }
+
+    if (uniforms.mirror != 0u) {
+        p.x = w - 1u - p.x;
+    }
+
+    let plane = min(channel, uniforms.pattern_channels - 1u);
+    return pattern[plane * w * h + p.y * w + p.x];
+}
+
+fn blend(old: f32, value: f32, weight: f32) -> f32 {
+    if (uniforms.blend == BLEND_ADD) {
+        return clamp(old + value * weight, 0.0, 1.0);
+    }
+    return mix(old, value, weight);
+}
+
+@compute
+@workgroup_size(16, 16)
+fn paint(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let x = global_id.x;
+    let y = global_id.y;
+    let channel = global_id.z;
+
+    if (x >= uniforms.width || y >= uniforms.height || channel >= uniforms.channels) {
+        return;
+    }
+
+    let plane_size = uniforms.width * uniforms.height;
+    let index = channel * plane_size + y * uniforms.width + x;
+    let old = output[index];
+
+    if (uniforms.use_pattern != 0u) {
+        // the pattern is centered on the brush, wrapping around the edges
+        let turned = uniforms.rotation % 2u == 1u;
+        let w = select(uniforms.pattern_width, uniforms.pattern_height, turned);
+        let h = select(uniforms.pattern_height, uniforms.pattern_width, turned);
+        let left = (uniforms.x + uniforms.width - (w / 2u) % uniforms.width) % uniforms.width;
+        let top = (uniforms.y + uniforms.height - (h / 2u) % uniforms.height) % uniforms.height;
+        let d = vec2<u32>((x + uniforms.width - left) % uniforms.width, (y + uniforms.height - top) % uniforms.height);
+
+        if (d.x >= w || d.y >= h) {
+            return;
+        }
+
+        output[index] = blend(old, pattern_cell(d, channel), 1.0);
+        return;
+    }
+
+    let offset = vec2<f32>(f32(wrapped_offset(uniforms.x, x, uniforms.width)), f32(wrapped_offset(uniforms.y, y, uniforms.height)));
+    let weight = coverage(offset);
+
+    if (weight <= 0.0) {
+        return;
+    }
+
+    switch uniforms.fill {
+        case FILL_CONSTANT: {
+            output[index] = blend(old, uniforms.value, weight);
+        }
+        case FILL_ERASER: {
+            // adding takes `value` away, replacing clears
+            if (uniforms.blend == BLEND_ADD) {
+                output[index] = clamp(old - uniforms.value * weight, 0.0, 1.0);
+            } else {
+                output[index] = mix(old, 0.0, weight);
+            }
+        }
+        default: {
+            let noise = cell_random(uniforms.seed, x, y, channel);
+            output[index] = blend(old, noise * uniforms.value, weight);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{storage_manager::Storage, uniforms_manager::Uniforms};
+use crate::{kernel_description::KernelDescription, storage_manager::Storage, uniforms_manager::Uniforms};
 
 pub struct ComputeState {
     pipeline: wgpu::ComputePipeline,
@@ -12,9 +12,10 @@ pub struct ComputeState {
 pub struct ComputeUniforms {
     pub height: u32,
     pub width: u32,
-    pub time_step: u32,
+    pub time_step: f32,
     pub m: f32,
     pub s: f32,
+    pub growth_function: u32,
     pub kernel_size: u32,
     pub kernel_sum: f32,
 }
@@ -29,7 +30,7 @@ impl ComputeState {
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Compute Bind Group"),
-            layout: &bind_group_layout,
+            layout: bind_group_layout,
             entries: &[
                 uniforms.bind_group_entry(0),
                 grid.bind_group_entry(1),
@@ -53,7 +54,7 @@ impl ComputeState {
     ) -> Self {
         let shader = device.create_shader_module(wgpu::include_wgsl!("compute.wgsl"));
 
-        let kernel_data = generate_kernel(5);
+        let kernel_data = KernelDescription { radius: 5, ..Default::default() }.generate();
 
         uniforms.kernel_size = kernel_data.len() as u32;
         uniforms.kernel_sum = kernel_data.iter().map(|r| r.iter().sum::<f32>()).sum();
@@ -112,33 +113,8 @@ impl ComputeState {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
 
-        let workgroups_x = (self.uniforms.width + 15) / 16;
-        let workgroups_y = (self.uniforms.height + 15) / 16;
+        let workgroups_x = self.uniforms.width.div_ceil(16);
+        let workgroups_y = self.uniforms.height.div_ceil(16);
         pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
     }
 }
-
-fn bell(x: f32, m: f32, s: f32) -> f32 {
-    (-(((x - m) / s).powi(2)) / 2.0).exp()
-}
-
-pub fn generate_kernel(radius: u32) -> Vec<Vec<f32>> {
-    let size = (2 * radius + 1) as usize;
-
-    let mut kernel = vec![vec![0f32; size]; size];
-
-    for i in 0..size {
-        for j in 0..size {
-            let y = (i as i32 - (radius + 1) as i32) as f32;
-            let x = (j as i32 - (radius + 1) as i32) as f32;
-            
-            let d = ((x + 1.0).powi(2) + (y + 1.0).powi(2)).sqrt() / radius as f32;
-        
-            if d < 1f32 { 
-                kernel[i][j] = bell(d, 0.5, 0.15)
-            }
-        }
-    }
-
-    kernel
-}
\ No newline at end of file
@@ -1,9 +1,10 @@
 struct ComputeUniforms {
     height: u32,
     width: u32,
-    time_step: u32,
+    time_step: f32,
     m: f32,
     s: f32,
+    growth_function: u32,
     kernel_size: u32,
     kernel_sum: f32,
 }
@@ -13,6 +14,35 @@ struct ComputeUniforms {
 @group(0) @binding(1) var<storage, read_write> in_out: array<f32>;
 @group(0) @binding(2) var<storage, read> kernel: array<f32>;
 
+const GROWTH_GAUSSIAN: u32 = 0u;
+const GROWTH_POLYNOMIAL: u32 = 1u;
+const GROWTH_RECTANGULAR: u32 = 2u;
+const GROWTH_LIFE: u32 = 3u;
+
+// maps a neighborhood sum to a growth rate in [-1, 1]
+fn growth_mapping(sum: f32, m: f32, s: f32, function: u32) -> f32 {
+    let z = (sum - m) / s;
+
+    switch function {
+        case GROWTH_POLYNOMIAL: {
+            return pow(max(1.0 - z * z / 9.0, 0.0), 4.0) * 2.0 - 1.0;
+        }
+        case GROWTH_RECTANGULAR: {
+            return select(-1.0, 1.0, abs(z) <= 1.0);
+        }
+        case GROWTH_LIFE: {
+            // birth band centered on m, survival band right below it
+            if (abs(z) <= 1.0) {
+                return 1.0;
+            }
+            return select(-1.0, 0.0, abs(z + 2.0) <= 1.0);
+        }
+        default: {
+            return exp(-0.5 * z * z) * 2.0 - 1.0;
+        }
+    }
+}
+
 var<workgroup> tile: array<f32, 4000>; // guaranteed minimum is only 4096, this is probably what causes flickering on mobile
 
 @compute
@@ -74,8 +104,7 @@ fn cs(
 
     let orig = tile[(u32(ly) + radius) * tile_size + (u32(lx) + radius)];
 
-    let z = (sum - uniforms.m) / uniforms.s;
-    let growth = exp(-0.5 * z * z) * 2.0 - 1.0;
+    let growth = growth_mapping(sum, uniforms.m, uniforms.s, uniforms.growth_function);
 
-    in_out[u32(gy) * uniforms.width + u32(gx)] = clamp(orig + (1.0/f32(uniforms.time_step)) * growth, 0.0, 1.0);
+    in_out[u32(gy) * uniforms.width + u32(gx)] = clamp(orig + (1.0 / uniforms.time_step) * growth, 0.0, 1.0);
 }
\ No newline at end of file
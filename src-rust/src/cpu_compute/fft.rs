@@ -0,0 +1,67 @@
+use std::f64::consts::PI;
+
+pub type Complex = [f64; 2];
+
+pub fn complex_mul(a: Complex, b: Complex) -> Complex {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+/// In place iterative radix 2 fft over every `size` long row of a plane, `size` has to be a power of two.
+/// Bit reversal reorders each row, then butterflies merge blocks of doubling length. The inverse is not normalized.
+pub fn fft_rows(data: &mut [Complex], size: usize, inverse: bool) {
+    let num_stages = size.ilog2();
+    // same sign convention as the shader, the forward transform uses the positive exponent
+    let angle_sign = if inverse { -1.0 } else { 1.0 };
+
+    for row in data.chunks_exact_mut(size) {
+        for i in 0..size {
+            let reversed = i.reverse_bits() >> (usize::BITS - num_stages);
+            if i < reversed {
+                row.swap(i, reversed);
+            }
+        }
+
+        let mut block_size = 2;
+        while block_size <= size {
+            let half_block = block_size / 2;
+
+            for block in row.chunks_exact_mut(block_size) {
+                for pos in 0..half_block {
+                    let angle = angle_sign * 2.0 * PI * pos as f64 / block_size as f64;
+                    let twiddle = [angle.cos(), angle.sin()];
+
+                    let a = block[pos];
+                    let wb = complex_mul(twiddle, block[pos + half_block]);
+
+                    block[pos] = [a[0] + wb[0], a[1] + wb[1]];
+                    block[pos + half_block] = [a[0] - wb[0], a[1] - wb[1]];
+                }
+            }
+
+            block_size *= 2;
+        }
+    }
+}
+
+/// Transposes a square plane in place
+pub fn transpose(data: &mut [Complex], size: usize) {
+    for y in 0..size {
+        for x in (y + 1)..size {
+            data.swap(y * size + x, x * size + y);
+        }
+    }
+}
+
+/// Row fft, transpose, row fft. The spectrum is left transposed, the gpu keeps half of it in place instead, see fft.wgsl.
+pub fn forward_2d(data: &mut [Complex], size: usize) {
+    fft_rows(data, size, false);
+    transpose(data, size);
+    fft_rows(data, size, false);
+}
+
+/// Undoes `forward_2d`, scaled by size^2
+pub fn inverse_2d(data: &mut [Complex], size: usize) {
+    fft_rows(data, size, true);
+    transpose(data, size);
+    fft_rows(data, size, true);
+}
@@ -0,0 +1,345 @@
+use anyhow::anyhow;
+
+use crate::{cpu_compute::fft::{Complex, complex_mul, forward_2d, inverse_2d}, fft_compute::{BoundaryMode, UpdateRule}, kernel_description::KernelDescription, simulation::SimulationConfig};
+
+pub mod fft;
+
+/// CPU version of `FFTComputeState`, runs the same passes with full complex square planes so the gpu results can be
+/// checked against it. Works on a grid of `config.channels` planes of width x height cells.
+pub struct CPUComputeState {
+    width: u32,
+    height: u32,
+    fft_size: u32,
+    /// fft of every kernel, one plane each, transposed by `forward_2d`
+    kernel_spectra: Vec<Vec<Complex>>,
+    config: SimulationConfig,
+}
+
+impl CPUComputeState {
+    pub fn new(
+        width: u32,
+        height: u32,
+        config: SimulationConfig,
+    ) -> anyhow::Result<Self> {
+        config.validate()?;
+
+        let fft_size = Self::square_size(width, height, config.max_radius());
+
+        let mut state = Self {
+            width,
+            height,
+            fft_size,
+            kernel_spectra: Vec::new(),
+            config,
+        };
+        state.create_kernel_spectra();
+
+        Ok(state)
+    }
+
+    fn create_kernel_spectra(&mut self) {
+        let size = self.fft_size as usize;
+
+        self.kernel_spectra = self.config.kernels
+            .iter()
+            .map(|k| {
+                let mut plane = k.kernel.wrapped(self.fft_size, self.fft_size).into_iter().map(|v| [v as f64, 0.0]).collect::<Vec<_>>();
+                forward_2d(&mut plane, size);
+                plane
+            })
+            .collect();
+    }
+
+    /// smallest power of two square that fits the grid plus `kernel_radius` ghost cells on every side.
+    /// The convolution doesn't depend on the padding, so this doesn't have to match the planes of `FFTComputeState`.
+    fn square_size(width: u32, height: u32, kernel_radius: u32) -> u32 {
+        (width.max(height) + 2 * kernel_radius).next_power_of_two()
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    pub fn fft_size(&self) -> u32 {
+        self.fft_size
+    }
+
+    pub fn handle_resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+
+        let fft_size = Self::square_size(width, height, self.config.max_radius());
+        if fft_size != self.fft_size {
+            self.fft_size = fft_size;
+            self.create_kernel_spectra();
+        }
+    }
+
+    pub fn set_kernel(&mut self, index: usize, kernel: KernelDescription) -> anyhow::Result<()> {
+        kernel.validate()?;
+
+        let kernel_count = self.config.kernels.len();
+        let channel_kernel = self.config.kernels
+            .get_mut(index)
+            .ok_or_else(|| anyhow!("there is no kernel {}, only {} kernels", index, kernel_count))?;
+        channel_kernel.kernel = kernel;
+
+        self.fft_size = Self::square_size(self.width, self.height, self.config.max_radius());
+        self.create_kernel_spectra();
+
+        Ok(())
+    }
+
+    /// same as `FFTComputeState::update`, the number of channels and kernels has to stay the same
+    pub fn update(&mut self, config: SimulationConfig) -> anyhow::Result<()> {
+        config.validate()?;
+
+        if config.channels != self.config.channels || config.kernels.len() != self.config.kernels.len() {
+            return Err(anyhow!(
+                "cannot update {} channels and {} kernels to {} channels and {} kernels in place",
+                self.config.channels, self.config.kernels.len(), config.channels, config.kernels.len()
+            ));
+        }
+
+        let kernels_changed = config.kernels
+            .iter()
+            .zip(&self.config.kernels)
+            .any(|(new, old)| new.kernel != old.kernel);
+
+        self.config = config;
+
+        if kernels_changed {
+            self.fft_size = Self::square_size(self.width, self.height, self.config.max_radius());
+            self.create_kernel_spectra();
+        }
+
+        Ok(())
+    }
+
+    pub fn set_boundary(&mut self, boundary: BoundaryMode) {
+        self.config.boundary = boundary;
+    }
+
+    /// full spectrum of every channel padded like pad_wrap.wgsl, transposed by `forward_2d`
+    pub fn spectra(&self, grid: &[f32]) -> Vec<Vec<Complex>> {
+        let size = self.fft_size as usize;
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let radius = self.config.max_radius() as i32;
+        let boundary = self.config.boundary;
+
+        grid.chunks_exact(width * height)
+            .take(self.config.channels as usize)
+            .map(|channel| {
+                // pad_wrap.wgsl, the grid at the origin surrounded by `radius` ghost cells
+                let mut plane = vec![[0.0; 2]; size * size];
+                for uy in -radius..height as i32 + radius {
+                    for ux in -radius..width as i32 + radius {
+                        let (Some(sx), Some(sy)) = (boundary.map(ux, self.width), boundary.map(uy, self.height)) else {
+                            continue;
+                        };
+
+                        let px = ux.rem_euclid(size as i32) as usize;
+                        let py = uy.rem_euclid(size as i32) as usize;
+                        plane[py * size + px] = [channel[sy as usize * width + sx as usize] as f64, 0.0];
+                    }
+                }
+
+                forward_2d(&mut plane, size);
+                plane
+            })
+            .collect()
+    }
+
+    /// neighborhood sums seen by the growth pass, one width x height plane per kernel
+    pub fn potential(&self, grid: &[f32]) -> Vec<Vec<f32>> {
+        let size = self.fft_size as usize;
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let scale = (size * size) as f64;
+
+        let spectra = self.spectra(grid);
+
+        self.config.kernels
+            .iter()
+            .zip(&self.kernel_spectra)
+            .map(|(kernel, kernel_spectrum)| {
+                // kernel.wgsl
+                let mut plane = spectra[kernel.source as usize]
+                    .iter()
+                    .zip(kernel_spectrum)
+                    .map(|(a, b)| complex_mul(*a, *b))
+                    .collect::<Vec<_>>();
+
+                inverse_2d(&mut plane, size);
+
+                let mut potential = vec![0f32; width * height];
+                for y in 0..height {
+                    for x in 0..width {
+                        potential[y * width + x] = (plane[y * size + x][0] / scale) as f32;
+                    }
+                }
+                potential
+            })
+            .collect()
+    }
+
+    /// advances the grid by one time step, like `FFTComputeState::run`
+    pub fn step(&self, grid: &mut [f32]) {
+        let plane_size = (self.width * self.height) as usize;
+        let dt = 1.0 / self.config.time_step;
+        let update_rule = self.config.update_rule;
+
+        let potential = self.potential(grid);
+
+        // growth.wgsl
+        for (channel, cells) in grid.chunks_exact_mut(plane_size).enumerate().take(self.config.channels as usize) {
+            let kernels = self.config.kernels
+                .iter()
+                .zip(&potential)
+                .filter(|(kernel, _)| kernel.target as usize == channel)
+                .collect::<Vec<_>>();
+
+            let mass_before = (update_rule == UpdateRule::MassConserving).then(|| cells.iter().sum::<f32>());
+
+            for (i, cell) in cells.iter_mut().enumerate() {
+                let growth: f32 = kernels
+                    .iter()
+                    .map(|(kernel, sums)| kernel.weight * self.config.growth_function.eval(sums[i], kernel.m, kernel.s))
+                    .sum();
+
+                *cell = update_rule.apply(*cell, growth, dt);
+            }
+
+            // conserve.wgsl
+            if let Some(mass_before) = mass_before {
+                let mass_after: f32 = cells.iter().sum();
+                if mass_after > 0.0 {
+                    for cell in cells.iter_mut() {
+                        *cell = (*cell * mass_before / mass_after).min(1.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{headless::noise, simulation::ChannelKernel};
+
+    use super::*;
+
+    /// potential of one kernel summed cell by cell over the wrapped neighborhood
+    fn direct_potential(kernel: &KernelDescription, channel: &[f32], width: u32, height: u32) -> Vec<f32> {
+        let radius = kernel.radius as i32;
+        let kernel_data = kernel.generate();
+        let kernel_sum: f64 = kernel_data.iter().flatten().map(|&v| v as f64).sum();
+
+        let mut potential = vec![0f32; (width * height) as usize];
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let mut sum = 0f64;
+                for (i, row) in kernel_data.iter().enumerate() {
+                    for (j, &value) in row.iter().enumerate() {
+                        let sy = (y + i as i32 - radius).rem_euclid(height as i32);
+                        let sx = (x + j as i32 - radius).rem_euclid(width as i32);
+                        sum += value as f64 * channel[(sy * width as i32 + sx) as usize] as f64;
+                    }
+                }
+                potential[(y * width as i32 + x) as usize] = (sum / kernel_sum) as f32;
+            }
+        }
+        potential
+    }
+
+    fn two_channel_config(radius: u32) -> SimulationConfig {
+        SimulationConfig {
+            channels: 2,
+            kernels: vec![
+                ChannelKernel {
+                    kernel: KernelDescription { radius, ..Default::default() },
+                    ..Default::default()
+                },
+                ChannelKernel {
+                    kernel: KernelDescription { radius: radius / 2 + 1, shells: vec![0.5, 1.0, 0.25], ..Default::default() },
+                    source: 1,
+                    target: 0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn potential_matches_direct_torus_convolution() {
+        // non square, not powers of two, and a kernel wider than the world
+        for (width, height, radius) in [(108, 100, 14), (72, 50, 9), (30, 20, 25)] {
+            let config = two_channel_config(radius);
+            let grid = noise(width, height, config.channels, width.max(height), 0.5, 3);
+            let state = CPUComputeState::new(width, height, config.clone()).unwrap();
+
+            let plane_size = (width * height) as usize;
+            for (kernel, potential) in config.kernels.iter().zip(state.potential(&grid)) {
+                let source = &grid[kernel.source as usize * plane_size..][..plane_size];
+                let expected = direct_potential(&kernel.kernel, source, width, height);
+
+                let max_diff = potential.iter().zip(&expected).map(|(a, b)| (a - b).abs()).fold(0f32, f32::max);
+                assert!(max_diff < 1e-5, "{}x{} radius {}: max diff {}", width, height, kernel.kernel.radius, max_diff);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_and_full_grids_stay_put() {
+        let (width, height) = (40, 30);
+
+        for update_rule in [UpdateRule::Euler, UpdateRule::SoftClip, UpdateRule::Asymptotic, UpdateRule::MassConserving] {
+            for time_step in [10.0, 50.0] {
+                // the growth is -1 at a potential of 0, and +1 at a potential of 1 with m at 1
+                let config = SimulationConfig {
+                    time_step,
+                    update_rule,
+                    kernels: vec![ChannelKernel { kernel: KernelDescription { radius: 9, ..Default::default() }, m: 1.0, s: 0.1, ..Default::default() }],
+                    ..Default::default()
+                };
+                let state = CPUComputeState::new(width, height, config).unwrap();
+
+                for value in [0.0, 1.0] {
+                    let mut grid = vec![value; (width * height) as usize];
+                    for _ in 0..5 {
+                        state.step(&mut grid);
+                    }
+
+                    let max_diff = grid.iter().map(|c| (c - value).abs()).fold(0f32, f32::max);
+                    assert!(max_diff < 1e-6, "{:?} T {}: a grid of {} moved by {}", update_rule, time_step, value, max_diff);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn step_applies_growth_to_direct_potential() {
+        let (width, height) = (90, 60);
+        let config = SimulationConfig {
+            kernels: vec![ChannelKernel { kernel: KernelDescription { radius: 13, ..Default::default() }, ..Default::default() }],
+            ..Default::default()
+        };
+        let state = CPUComputeState::new(width, height, config.clone()).unwrap();
+
+        let mut grid = noise(width, height, 1, 52, 0.5, 11);
+        let potential = direct_potential(&config.kernels[0].kernel, &grid, width, height);
+        let kernel = &config.kernels[0];
+        let expected = grid
+            .iter()
+            .zip(&potential)
+            .map(|(&cell, &sum)| config.update_rule.apply(cell, config.growth_function.eval(sum, kernel.m, kernel.s), 1.0 / config.time_step))
+            .collect::<Vec<_>>();
+
+        state.step(&mut grid);
+
+        let max_diff = grid.iter().zip(&expected).map(|(a, b)| (a - b).abs()).fold(0f32, f32::max);
+        assert!(max_diff < 1e-5, "max diff {}", max_diff);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{fft_compute::FFTBuffers, storage_manager::Storage, uniforms_manager::Uniforms};
+
+/// Rescales every channel back to the mass it had before the growth pass, for `UpdateRule::MassConserving`
+pub struct ConserveState {
+    measure_before_pipeline: wgpu::ComputePipeline,
+    measure_after_pipeline: wgpu::ComputePipeline,
+    rescale_pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    /// mass of each channel before and after the growth pass
+    masses: Storage,
+    pub uniforms: Uniforms<ConserveUniforms>,
+}
+
+#[derive(Clone, Copy, Debug, encase::ShaderType)]
+pub struct ConserveUniforms {
+    pub fft_width: u32,
+    pub fft_height: u32,
+    pub height: u32,
+    pub width: u32,
+    pub channels: u32,
+}
+
+impl ConserveState {
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, grid: &Storage, buffers: &FFTBuffers, masses: &Storage, uniforms: &Uniforms<ConserveUniforms>) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Conserve Bind Group"),
+            layout,
+            entries: &[
+                uniforms.bind_group_entry(0),
+                grid.bind_group_entry(1),
+                buffers.fields.bind_group_entry(2),
+                masses.bind_group_entry(3),
+            ]
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        buffers: &FFTBuffers,
+        grid: &Storage,
+        uniforms: ConserveUniforms
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("conserve.wgsl"));
+
+        let uniforms = Uniforms::new(device, "Conserve", uniforms);
+        let masses = Storage::new_empty(device, "Channel Masses", uniforms.channels as u64 * 2 * 4);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Conserve Bind Group Layout"),
+            entries: &[
+                uniforms.layout_entry(0, wgpu::ShaderStages::COMPUTE),
+                grid.layout_entry(1, wgpu::ShaderStages::COMPUTE, false),
+                buffers.fields.layout_entry(2, wgpu::ShaderStages::COMPUTE, false),
+                masses.layout_entry(3, wgpu::ShaderStages::COMPUTE, false),
+            ]
+        });
+
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, grid, buffers, &masses, &uniforms);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Conserve Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+
+        let pipeline = |entry_point| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Conserve Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            cache: None
+        });
+
+        Self {
+            measure_before_pipeline: pipeline("measure_before"),
+            measure_after_pipeline: pipeline("measure_after"),
+            rescale_pipeline: pipeline("rescale"),
+            bind_group_layout,
+            bind_group,
+            masses,
+            uniforms
+        }
+    }
+
+    pub fn recreate_bind_groups(
+        &mut self,
+        device: &wgpu::Device,
+        grid: &Storage,
+        buffers: &FFTBuffers
+    ) {
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, grid, buffers, &self.masses, &self.uniforms);
+    }
+
+    /// records the mass of every channel, has to run before the growth pass
+    pub fn run_before(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+    ) {
+        self.uniforms.write(queue);
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Conserve Measure Pass"),
+            timestamp_writes: None
+        });
+
+        pass.set_pipeline(&self.measure_before_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(self.uniforms.channels, 1, 1);
+    }
+
+    /// rescales the channels grown by the growth pass to their mass from `run_before`
+    pub fn run_after(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Conserve Rescale Pass"),
+            timestamp_writes: None
+        });
+
+        pass.set_bind_group(0, &self.bind_group, &[]);
+
+        pass.set_pipeline(&self.measure_after_pipeline);
+        pass.dispatch_workgroups(self.uniforms.channels, 1, 1);
+
+        pass.set_pipeline(&self.rescale_pipeline);
+        let workgroups_x = self.uniforms.width.div_ceil(16);
+        let workgroups_y = self.uniforms.height.div_ceil(16);
+        pass.dispatch_workgroups(workgroups_x, workgroups_y, self.uniforms.channels);
+    }
+}
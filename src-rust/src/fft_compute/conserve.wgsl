@@ -0,0 +1,91 @@
+struct ConserveUniforms {
+    fft_width: u32,
+    fft_height: u32,
+    height: u32,
+    width: u32,
+    channels: u32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: ConserveUniforms;
+@group(0) @binding(1) var<storage, read_write> grid: array<f32>;
+// growth and change of every cell, the change is corrected for the rescale
+@group(0) @binding(2) var<storage, read_write> fields: array<vec2<f32>>;
+// mass of every channel before and after the growth pass
+@group(0) @binding(3) var<storage, read_write> masses: array<vec2<f32>>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+var<workgroup> sums: array<f32, WORKGROUP_SIZE>;
+
+// one workgroup per channel sums its plane
+fn channel_mass(local: u32, channel: u32) -> f32 {
+    let plane_size = uniforms.width * uniforms.height;
+
+    var sum = 0.0;
+    for (var i = local; i < plane_size; i += WORKGROUP_SIZE) {
+        sum += grid[channel * plane_size + i];
+    }
+    sums[local] = sum;
+
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if (local < stride) {
+            sums[local] += sums[local + stride];
+        }
+        workgroupBarrier();
+    }
+
+    return sums[0];
+}
+
+@compute
+@workgroup_size(WORKGROUP_SIZE)
+fn measure_before(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+) {
+    let mass = channel_mass(local, workgroup.x);
+    if (local == 0u) {
+        masses[workgroup.x].x = mass;
+    }
+}
+
+@compute
+@workgroup_size(WORKGROUP_SIZE)
+fn measure_after(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+) {
+    let mass = channel_mass(local, workgroup.x);
+    if (local == 0u) {
+        masses[workgroup.x].y = mass;
+    }
+}
+
+@compute
+@workgroup_size(16, 16)
+fn rescale(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let x = global_id.x;
+    let y = global_id.y;
+    let channel = global_id.z;
+
+    if (x >= uniforms.width || y >= uniforms.height || channel >= uniforms.channels) {
+        return;
+    }
+
+    let mass = masses[channel];
+    if (mass.y <= 0.0) {
+        return;
+    }
+
+    let idx = channel * uniforms.width * uniforms.height + y * uniforms.width + x;
+    let updated = grid[idx];
+    let rescaled = min(updated * mass.x / mass.y, 1.0);
+    grid[idx] = rescaled;
+
+    let field_idx = channel * uniforms.fft_width * uniforms.fft_height + y * uniforms.fft_width + x;
+    fields[field_idx].y += rescaled - updated;
+}
@@ -1,17 +1,102 @@
 use crate::{storage_manager::Storage, uniforms_manager::Uniforms};
 
+/// 2D fft of real planes, see fft.wgsl for the layout of the buffer
 pub struct FFTState {
-    pipeline_forward: wgpu::ComputePipeline,
-    pipeline_inverse: wgpu::ComputePipeline,
+    pipeline_rows_forward: wgpu::ComputePipeline,
+    pipeline_columns_forward: wgpu::ComputePipeline,
+    pipeline_columns_inverse: wgpu::ComputePipeline,
+    pipeline_rows_inverse: wgpu::ComputePipeline,
+    pipeline_rows_pack: wgpu::ComputePipeline,
+    pipeline_rows_split: wgpu::ComputePipeline,
+    pipeline_rows_join: wgpu::ComputePipeline,
+    pipeline_rows_unpack: wgpu::ComputePipeline,
+    pipeline_rows_stage: wgpu::ComputePipeline,
+    pipeline_columns_stage: wgpu::ComputePipeline,
+    pipeline_transpose_to_columns: wgpu::ComputePipeline,
+    pipeline_transpose_to_rows: wgpu::ComputePipeline,
     bind_group_layout: wgpu::BindGroupLayout,
-    bind_group: wgpu::BindGroup,
+    /// longest row or column transformed in workgroup memory, see `SHARED_SIZE` in fft.wgsl
+    shared_len: u32,
+    forward_steps: Vec<FFTStep>,
+    inverse_steps: Vec<FFTStep>,
     pub uniforms: Uniforms<FFTUniforms>,
 }
 
+#[derive(Clone, Copy, Debug, encase::ShaderType)]
+struct FFTStage {
+    span: u32,
+    inverse: u32,
+    transpose: u32,
+}
+
+/// same as `TILE_SIZE` in fft.wgsl
+const TILE_SIZE: u32 = 16;
+
+enum FFTStep {
+    Dispatch {
+        pipeline: wgpu::ComputePipeline,
+        bind_group: wgpu::BindGroup,
+        workgroups: (u32, u32, u32),
+    },
+    /// hands the result of the stage passes over between the fft buffer and the scratch buffer
+    Copy {
+        source: wgpu::Buffer,
+        destination: wgpu::Buffer,
+    },
+}
+
 #[derive(Clone, Copy, Debug, encase::ShaderType)]
 pub struct FFTUniforms {
-    pub size: u32,       // power of 2, must be >= 2 * WORKGROUP_SIZE
-    pub num_stages: u32, // log2 size
+    pub width: u32,  // length of the rows, see `FFTUniforms::padded_len`
+    pub height: u32, // length of the columns, see `FFTUniforms::padded_len`
+    pub planes: u32, // number of width x height matrices stacked in the buffer
+}
+
+impl FFTUniforms {
+    /// smallest length of at least `min_len` the fft handles, an even product of 2, 3 and 5
+    pub fn padded_len(min_len: u32) -> u32 {
+        let is_smooth = |mut rest: u32| {
+            for radix in [2, 3, 5] {
+                while rest.is_multiple_of(radix) {
+                    rest /= radix;
+                }
+            }
+            rest == 1
+        };
+
+        (min_len.max(2)..).find(|&len| len.is_multiple_of(2) && is_smooth(len)).unwrap()
+    }
+
+    /// complex numbers per row of a plane, the half spectrum of a real row
+    pub fn half_size(width: u32) -> u32 {
+        width / 2 + 1
+    }
+
+    /// floats per row of a plane holding real cells, the cells are followed by 2 floats of padding
+    pub fn real_stride(width: u32) -> u32 {
+        2 * Self::half_size(width)
+    }
+
+    /// bytes of one plane, `height` rows of `half_size` complex numbers
+    pub fn plane_bytes(width: u32, height: u32) -> u64 {
+        (height * Self::half_size(width) * 4 * 2) as u64
+    }
+}
+
+/// same as `next_radix` in fft.wgsl
+fn next_radix(span: u32) -> u32 {
+    [5, 3].into_iter().find(|radix| span.is_multiple_of(*radix)).unwrap_or(2)
+}
+
+/// lengths of the sub-transforms merged by the stockham passes of a transform of length `len`
+fn stage_spans(len: u32) -> Vec<u32> {
+    let mut spans = Vec::new();
+    let mut span = 1;
+    while span < len {
+        spans.push(span);
+        span *= next_radix(len / span);
+    }
+    spans
 }
 
 impl FFTState {
@@ -23,99 +108,279 @@ impl FFTState {
         let shader = device.create_shader_module(wgpu::include_wgsl!("fft.wgsl"));
 
         let uniforms = Uniforms::new(device, "FFT", uniforms);
+        let stage = Uniforms::new(device, "FFT Stage", FFTStage { span: 0, inverse: 0, transpose: 0 });
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("FFT Bind Group Layout"),
             entries: &[
                 uniforms.layout_entry(0, wgpu::ShaderStages::COMPUTE),
                 fft_buffer.layout_entry(1, wgpu::ShaderStages::COMPUTE, false),
+                fft_buffer.layout_entry(2, wgpu::ShaderStages::COMPUTE, false),
+                stage.layout_entry(3, wgpu::ShaderStages::COMPUTE),
             ],
         });
 
-        let bind_group = Self::create_bind_group(device, &bind_group_layout, &uniforms, fft_buffer);
-
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("FFT Pipeline Layout"),
             bind_group_layouts: &[&bind_group_layout],
             push_constant_ranges: &[],
         });
 
-        let pipeline_inverse = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("FFT Pipeline"),
-            layout: Some(&pipeline_layout),
-            module: &shader,
-            entry_point: Some("fft_inverse"),
-            compilation_options: Default::default(),
-            cache: Default::default(),
-        });
+        // rows and columns that fit the workgroup memory are transformed in one pass
+        let shared_len = device.limits().max_compute_workgroup_storage_size / 8;
 
-        let pipeline_forward = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        let create_pipeline = |entry_point| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
             label: Some("FFT Pipeline"),
             layout: Some(&pipeline_layout),
             module: &shader,
-            entry_point: Some("fft_forward"),
-            compilation_options: Default::default(),
+            entry_point: Some(entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &[("SHARED_SIZE", shared_len as f64)],
+                ..Default::default()
+            },
             cache: Default::default(),
         });
 
-        Self {
-            pipeline_forward,
-            pipeline_inverse,
+        let mut state = Self {
+            pipeline_rows_forward: create_pipeline("rows_forward"),
+            pipeline_columns_forward: create_pipeline("columns_forward"),
+            pipeline_columns_inverse: create_pipeline("columns_inverse"),
+            pipeline_rows_inverse: create_pipeline("rows_inverse"),
+            pipeline_rows_pack: create_pipeline("rows_pack"),
+            pipeline_rows_split: create_pipeline("rows_split"),
+            pipeline_rows_join: create_pipeline("rows_join"),
+            pipeline_rows_unpack: create_pipeline("rows_unpack"),
+            pipeline_rows_stage: create_pipeline("rows_stage"),
+            pipeline_columns_stage: create_pipeline("columns_stage"),
+            pipeline_transpose_to_columns: create_pipeline("transpose_to_columns"),
+            pipeline_transpose_to_rows: create_pipeline("transpose_to_rows"),
             bind_group_layout,
-            bind_group,
+            shared_len,
+            forward_steps: Vec::new(),
+            inverse_steps: Vec::new(),
             uniforms,
+        };
+        state.recreate_bind_groups(device, fft_buffer);
+        state
+    }
+
+    /// limits to request the device with, large worlds need the bigger buffers and workgroup memory of the adapter
+    pub fn required_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+        let limits = adapter.limits();
+
+        // llvmpipe announces 32 KiB of workgroup memory but hangs on anything above the default 16 KiB
+        if adapter.get_info().device_type == wgpu::DeviceType::Cpu {
+            return wgpu::Limits {
+                max_compute_workgroup_storage_size: wgpu::Limits::default().max_compute_workgroup_storage_size,
+                ..limits
+            };
         }
+
+        limits
     }
 
     fn create_bind_group(
+        &self,
         device: &wgpu::Device,
-        bind_group_layout: &wgpu::BindGroupLayout,
-        uniforms: &Uniforms<FFTUniforms>,
-        fft_buffer: &Storage,
+        in_out: &wgpu::Buffer,
+        scratch: &wgpu::Buffer,
+        stage: FFTStage,
     ) -> wgpu::BindGroup {
+        let stage = Uniforms::new(device, "FFT Stage", stage);
+
         device.create_bind_group(&wgpu::BindGroupDescriptor {   
             label: Some("FFT Bind Group"),
-            layout: &bind_group_layout,
+            layout: &self.bind_group_layout,
             entries: &[
-                uniforms.bind_group_entry(0),
-                fft_buffer.bind_group_entry(1),
+                self.uniforms.bind_group_entry(0),
+                wgpu::BindGroupEntry { binding: 1, resource: in_out.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: scratch.as_entire_binding() },
+                stage.bind_group_entry(3),
             ],
         })
     }
 
+    /// rebuilds the passes for the current uniforms, the scratch buffer only takes up memory if a row or column is
+    /// longer than `shared_len`
     pub fn recreate_bind_groups(
         &mut self,
         device: &wgpu::Device,
         fft_buffer: &Storage,
     ) {
-        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.uniforms, fft_buffer)
+        let FFTUniforms { width, height, .. } = *self.uniforms;
+
+        let scratch_size = if width.max(height) > self.shared_len { fft_buffer.buffer().size() } else { 8 };
+        let scratch = Storage::new_empty(device, "FFT Scratch", scratch_size);
+
+        let mut forward_steps = self.row_steps(device, fft_buffer.buffer(), scratch.buffer(), false);
+        forward_steps.extend(self.column_steps(device, fft_buffer.buffer(), scratch.buffer(), false));
+
+        let mut inverse_steps = self.column_steps(device, fft_buffer.buffer(), scratch.buffer(), true);
+        inverse_steps.extend(self.row_steps(device, fft_buffer.buffer(), scratch.buffer(), true));
+
+        self.forward_steps = forward_steps;
+        self.inverse_steps = inverse_steps;
     }
 
-    pub fn run_forward(
+    /// one stockham pass per radix of a transform of length `len`, swapping `fft_buffer` and `scratch` in between.
+    /// `transpose` fuses the transpose of the columns into the first pass. Returns whether the result ended up in `scratch`.
+    #[allow(clippy::too_many_arguments)]
+    fn stage_steps(
+        &self,
+        device: &wgpu::Device,
+        steps: &mut Vec<FFTStep>,
+        pipeline: &wgpu::ComputePipeline,
+        fft_buffer: &wgpu::Buffer,
+        scratch: &wgpu::Buffer,
+        mut in_scratch: bool,
+        len: u32,
+        lines: u32,
+        inverse: bool,
+        transpose: bool,
+    ) -> bool {
+        for (i, span) in stage_spans(len).into_iter().enumerate() {
+            let (in_out, destination) = if in_scratch { (scratch, fft_buffer) } else { (fft_buffer, scratch) };
+            let stage = FFTStage { span, inverse: inverse as u32, transpose: (transpose && i == 0) as u32 };
+
+            steps.push(FFTStep::Dispatch {
+                pipeline: pipeline.clone(),
+                bind_group: self.create_bind_group(device, in_out, destination, stage),
+                workgroups: ((len / 2).div_ceil(256), lines, self.uniforms.planes),
+            });
+
+            in_scratch = !in_scratch;
+        }
+
+        in_scratch
+    }
+
+    fn row_steps(
+        &self,
+        device: &wgpu::Device,
+        fft_buffer: &wgpu::Buffer,
+        scratch: &wgpu::Buffer,
+        inverse: bool,
+    ) -> Vec<FFTStep> {
+        let FFTUniforms { width, height, planes } = *self.uniforms;
+        let bind_group = self.create_bind_group(device, fft_buffer, scratch, FFTStage { span: 0, inverse: inverse as u32, transpose: 0 });
+
+        // a workgroup per pair of rows
+        if width <= self.shared_len {
+            let pipeline = if inverse { &self.pipeline_rows_inverse } else { &self.pipeline_rows_forward };
+            return vec![FFTStep::Dispatch { pipeline: pipeline.clone(), bind_group, workgroups: (height / 2, planes, 1) }];
+        }
+
+        // the packed complex rows are built in scratch, transformed and pulled apart back into the fft buffer
+        let (first, last) = if inverse {
+            (&self.pipeline_rows_join, &self.pipeline_rows_unpack)
+        } else {
+            (&self.pipeline_rows_pack, &self.pipeline_rows_split)
+        };
+
+        let mut steps = vec![FFTStep::Dispatch {
+            pipeline: first.clone(),
+            bind_group: bind_group.clone(),
+            workgroups: (width.div_ceil(256), height / 2, planes),
+        }];
+
+        let in_scratch = self.stage_steps(device, &mut steps, &self.pipeline_rows_stage, fft_buffer, scratch, true, width, height / 2, inverse, false);
+        if !in_scratch {
+            steps.push(FFTStep::Copy { source: fft_buffer.clone(), destination: scratch.clone() });
+        }
+
+        steps.push(FFTStep::Dispatch {
+            pipeline: last.clone(),
+            bind_group,
+            workgroups: (FFTUniforms::half_size(width).div_ceil(256), height / 2, planes),
+        });
+
+        steps
+    }
+
+    fn column_steps(
+        &self,
+        device: &wgpu::Device,
+        fft_buffer: &wgpu::Buffer,
+        scratch: &wgpu::Buffer,
+        inverse: bool,
+    ) -> Vec<FFTStep> {
+        let FFTUniforms { width, height, planes } = *self.uniforms;
+        let half = FFTUniforms::half_size(width);
+
+        let bind_group = self.create_bind_group(device, fft_buffer, scratch, FFTStage { span: 0, inverse: inverse as u32, transpose: 0 });
+
+        // a workgroup per tile of adjacent columns of the half spectra, see `column_tile` in fft.wgsl
+        if height <= self.shared_len {
+            let pipeline = if inverse { &self.pipeline_columns_inverse } else { &self.pipeline_columns_forward };
+            let tile = (self.shared_len / height).clamp(1, TILE_SIZE);
+            return vec![FFTStep::Dispatch { pipeline: pipeline.clone(), bind_group, workgroups: (half.div_ceil(tile), planes, 1) }];
+        }
+
+        // the stage passes run over the transposed half spectra in scratch, so the elements of a column are next to each other.
+        // An odd number of passes transposes in the first one to still end up in scratch.
+        let transpose = stage_spans(height).len() % 2 == 1;
+
+        let mut steps = Vec::new();
+        if !transpose {
+            steps.push(FFTStep::Dispatch {
+                pipeline: self.pipeline_transpose_to_columns.clone(),
+                bind_group,
+                workgroups: (half.div_ceil(TILE_SIZE), height.div_ceil(TILE_SIZE), planes),
+            });
+        }
+
+        let in_scratch = self.stage_steps(device, &mut steps, &self.pipeline_columns_stage, fft_buffer, scratch, !transpose, height, half, inverse, transpose);
+        debug_assert!(in_scratch);
+
+        steps.push(FFTStep::Dispatch {
+            pipeline: self.pipeline_transpose_to_rows.clone(),
+            bind_group: self.create_bind_group(device, scratch, fft_buffer, FFTStage { span: 0, inverse: inverse as u32, transpose: 0 }),
+            workgroups: (height.div_ceil(TILE_SIZE), half.div_ceil(TILE_SIZE), planes),
+        });
+
+        steps
+    }
+
+    fn run_steps(
         &self,
         encoder: &mut wgpu::CommandEncoder,
         queue: &wgpu::Queue,
+        steps: &[FFTStep],
+        label: &str,
     ) {
         self.uniforms.write(queue);
 
-        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("FFT Forward Compute Pass"), timestamp_writes: None });
+        for step in steps {
+            match step {
+                FFTStep::Dispatch { pipeline, bind_group, workgroups: (x, y, z) } => {
+                    let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some(label), timestamp_writes: None });
 
-        pass.set_pipeline(&self.pipeline_forward);
-        pass.set_bind_group(0, &self.bind_group, &[]);
-        pass.dispatch_workgroups(self.uniforms.size, 1, 1);
+                    pass.set_pipeline(pipeline);
+                    pass.set_bind_group(0, bind_group, &[]);
+                    pass.dispatch_workgroups(*x, *y, *z);
+                }
+                FFTStep::Copy { source, destination } => {
+                    encoder.copy_buffer_to_buffer(source, 0, destination, 0, source.size());
+                }
+            }
+        }
     }
 
-    pub fn run_inverse(
+    /// real cells to half spectra
+    pub fn run_forward(
         &self,
         encoder: &mut wgpu::CommandEncoder,
         queue: &wgpu::Queue,
     ) {
-        self.uniforms.write(queue);
-
-        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("FFT Inverse Compute Pass"), timestamp_writes: None });
+        self.run_steps(encoder, queue, &self.forward_steps, "FFT Forward Compute Pass");
+    }
 
-        pass.set_pipeline(&self.pipeline_inverse);
-        pass.set_bind_group(0, &self.bind_group, &[]);
-        pass.dispatch_workgroups(self.uniforms.size, 1, 1);
+    /// half spectra back to real cells, scaled by width * height
+    pub fn run_inverse(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+    ) {
+        self.run_steps(encoder, queue, &self.inverse_steps, "FFT Inverse Compute Pass");
     }
 }
@@ -1,17 +1,36 @@
 struct FFTUniforms {
-    size: u32, // power of 2, must be >= 2 * WORKGROUP_SIZE
-    num_stages: u32, // log2 size
+    width: u32, // length of the rows, an even product of 2, 3 and 5
+    height: u32, // length of the columns, an even product of 2, 3 and 5
+    planes: u32,
+}
+
+// one radix pass of the global memory transform
+struct FFTStage {
+    span: u32, // length of the sub-transforms merged by this pass
+    inverse: u32,
+    transpose: u32, // the columns pass reads the half spectra in place instead of the transposed copy, see `columns_stage`
 }
 
 const PI: f32 = 3.14159265;
-const MAX_SIZE: u32 = 2048u; // guaranteed minimum workgroup memory size is 4096
 // const WORKGROUP_SIZE: u32 = 1u;
 const WORKGROUP_SIZE: u32 = 256u;
+const MAX_RADIX: u32 = 5u;
+const TILE_SIZE: u32 = 16u;
+
+// complex numbers that fit the workgroup memory of the device, longer rows and columns go through the stage passes
+override SHARED_SIZE: u32 = 2048u; // guaranteed minimum workgroup memory size is 16384 bytes
 
-var<workgroup> shared_data: array<vec2<f32>, MAX_SIZE>; 
+var<workgroup> shared_data: array<vec2<f32>, SHARED_SIZE>;
+// one column of padding keeps the threads of a column off the same memory bank
+var<workgroup> transpose_data: array<vec2<f32>, 272>; // TILE_SIZE * (TILE_SIZE + 1)
 
 @group(0) @binding(0) var<uniform> uniforms: FFTUniforms;
+// every plane is `height` rows of `width / 2 + 1` complex numbers, the half spectrum of the rows of a real plane.
+// Before the forward / after the inverse transform each row holds `width` real cells instead, followed by 2 floats of padding.
 @group(0) @binding(1) var<storage, read_write> in_out: array<vec2<f32>>;
+// same size as in_out when rows or columns are longer than SHARED_SIZE, the stage passes read one and write the other
+@group(0) @binding(2) var<storage, read_write> scratch: array<vec2<f32>>;
+@group(0) @binding(3) var<uniform> stage: FFTStage;
 
 
 // Complex multiplication: (a + bi) * (c + di) = (ac - bd) + (ad + bc)i
@@ -19,106 +38,447 @@ fn complex_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
     return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
 }
 
-// Bit reversal for indices
-fn bit_reverse(x: u32, num_bits: u32) -> u32 {
-    var result = 0u;
-    var value = x;
-    for (var i = 0u; i < num_bits; i++) {
-        result = (result << 1u) | (value & 1u);
-        value = value >> 1u;
+fn conj(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x, -a.y);
+}
+
+fn twiddle(turns: f32) -> vec2<f32> {
+    let angle = 2.0 * PI * turns;
+    return vec2<f32>(cos(angle), sin(angle));
+}
+
+// radix of the next stage of a transform with `span` elements left per sub-transform, fives first, then threes and twos
+fn next_radix(span: u32) -> u32 {
+    if (span % 5u == 0u) {
+        return 5u;
+    }
+    if (span % 3u == 0u) {
+        return 3u;
+    }
+    return 2u;
+}
+
+// index of the input element that goes to position p of shared_data, the mixed radix version of a bit reversal
+fn digit_reverse(p: u32, n: u32) -> u32 {
+    var index = 0u;
+    var rest = p;
+    var span = n;
+
+    while (span > 1u) {
+        let radix = next_radix(span);
+        span /= radix;
+        index += (rest % radix) * span;
+        rest /= radix;
     }
-    return result;
+
+    return index;
+}
+
+// complex numbers per row of a plane
+fn half_size() -> u32 {
+    return uniforms.width / 2u + 1u;
 }
 
-fn fft_1d(
-    global_id: vec3<u32>,
-    local_id: vec3<u32>,
-    workgroup_id: vec3<u32>,
-    inverse: bool,
+fn plane_offset(plane: u32) -> u32 {
+    return plane * uniforms.height * half_size();
+}
+
+// dft of length radix
+fn small_dft(inputs: array<vec2<f32>, MAX_RADIX>, radix: u32, sign: f32) -> array<vec2<f32>, MAX_RADIX> {
+    var outputs: array<vec2<f32>, MAX_RADIX>;
+    for (var k = 0u; k < radix; k++) {
+        var sum = vec2<f32>(0.0, 0.0);
+        for (var q = 0u; q < radix; q++) {
+            sum += complex_mul(twiddle(sign * f32((q * k) % radix) / f32(radix)), inputs[q]);
+        }
+        outputs[k] = sum;
+    }
+    return outputs;
+}
+
+// transforms `count` sequences of n elements at the start of shared_data in place, which have to be loaded in digit reversed order
+fn fft_shared(n: u32, count: u32, thread_id: u32, inverse: bool) {
+    let sign = select(1.0, -1.0, inverse);
+
+    // every stage merges `radix` transforms of length m into one of length m * radix
+    var m = 1u;
+    while (m < n) {
+        let radix = next_radix(n / m);
+        let block_size = m * radix;
+
+        // sizes below radix * WORKGROUP_SIZE leave some threads without a butterfly
+        for (var butterfly_id = thread_id; butterfly_id < count * n / radix; butterfly_id += WORKGROUP_SIZE) {
+            let sequence = butterfly_id / (n / radix);
+            let local_id = butterfly_id % (n / radix);
+            let j = local_id % m;
+            let start = sequence * n + (local_id / m) * block_size + j;
+
+            var inputs: array<vec2<f32>, MAX_RADIX>;
+            for (var q = 0u; q < radix; q++) {
+                let w = twiddle(sign * f32((j * q) % block_size) / f32(block_size));
+                inputs[q] = complex_mul(w, shared_data[start + q * m]);
+            }
+
+            let outputs = small_dft(inputs, radix, sign);
+            for (var k = 0u; k < radix; k++) {
+                shared_data[start + k * m] = outputs[k];
+            }
+        }
+
+        workgroupBarrier();
+        m = block_size;
+    }
+}
+
+// real cell x of a row, rows of real cells are packed two cells per complex number
+fn load_real(row_offset: u32, x: u32) -> f32 {
+    return in_out[row_offset + x / 2u][x % 2u];
+}
+
+// two real rows are transformed at once as the real and imaginary part of one complex row,
+// their half spectra are pulled apart with the symmetry of the spectrum of a real row
+@compute
+@workgroup_size(256)
+fn rows_forward(
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
 ) {
-    let row = workgroup_id.x; 
+    let n = uniforms.width;
+    let half = half_size();
     let thread_id = local_id.x;
 
-    let num_stages = uniforms.num_stages;
-    let n = uniforms.size;
-    let angle_sign = select(1.0, -1.0, inverse);
+    let first = plane_offset(workgroup_id.y) + 2u * workgroup_id.x * half;
+    let second = first + half;
 
-    let elements_per_thread = n / WORKGROUP_SIZE; // will always divide evenly, since input is padded
-
-    // Load row into shared memory
-    for (var i = 0u; i < elements_per_thread; i++) {
-        let idx = thread_id + i * WORKGROUP_SIZE;  // I am suspicious of this...
-        // bit reverse index when writing to shared memory 
-        let reversed_idx = bit_reverse(idx, num_stages);  
-        shared_data[idx] = in_out[row * n + reversed_idx]; // TODO: scattered reads here ...
+    for (var idx = thread_id; idx < n; idx += WORKGROUP_SIZE) {
+        let x = digit_reverse(idx, n);
+        shared_data[idx] = vec2<f32>(load_real(first, x), load_real(second, x));
     }
 
     workgroupBarrier();
 
-    for (var stage = 0u; stage < num_stages; stage++) {
-        let block_size = 1u << (stage + 1u);  // 2, 4, 8, 16, 32, ...
-        let half_block = block_size >> 1u;
+    fft_shared(n, 1u, thread_id, false);
+
+    // both rows were read before the barriers, so the spectra can take their place
+    for (var k = thread_id; k < half; k += WORKGROUP_SIZE) {
+        let z = shared_data[k];
+        let mirrored = conj(shared_data[(n - k) % n]);
 
-        let total_butterflies = n / 2u;
-        let butterflies_per_thread = total_butterflies / WORKGROUP_SIZE;
+        let sum = z + mirrored;
+        let difference = z - mirrored;
 
-        for (var i = 0u; i < butterflies_per_thread; i++) {
-            let butterfly_id = thread_id + i * WORKGROUP_SIZE;
-            
-            // Map butterfly_id to element pair
-            let block_idx = butterfly_id / half_block;
-            let pos_in_block = butterfly_id % half_block;
+        in_out[first + k] = sum * 0.5;
+        in_out[second + k] = vec2<f32>(difference.y, -difference.x) * 0.5; // -i (z - mirrored) / 2
+    }
+}
 
-            let idx_a = block_idx * block_size + pos_in_block;
-            let idx_b = idx_a + half_block;
+// undoes rows_forward, the spectra of two rows are joined into one complex row whose inverse holds both rows
+@compute
+@workgroup_size(256)
+fn rows_inverse(
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let n = uniforms.width;
+    let half = half_size();
+    let thread_id = local_id.x;
 
-            // Compute twiddle factor
-            let angle = angle_sign * 2.0 * PI * f32(pos_in_block) / f32(block_size);
-            let twiddle = vec2<f32>(cos(angle), sin(angle));
+    let first = plane_offset(workgroup_id.y) + 2u * workgroup_id.x * half;
+    let second = first + half;
 
-            let a = shared_data[idx_a];
-            let b = shared_data[idx_b];
+    for (var idx = thread_id; idx < n; idx += WORKGROUP_SIZE) {
+        let k = digit_reverse(idx, n);
 
-            let wb = complex_mul(twiddle, b);
-                
-            // Butterfly operation
-            shared_data[idx_a] = vec2<f32>(a.x + wb.x, a.y + wb.y);
-            shared_data[idx_b] = vec2<f32>(a.x - wb.x, a.y - wb.y);
+        // the upper half of the spectrum of a real row mirrors the lower half
+        var a: vec2<f32>;
+        var b: vec2<f32>;
+        if (k < half) {
+            a = in_out[first + k];
+            b = in_out[second + k];
+        } else {
+            a = conj(in_out[first + n - k]);
+            b = conj(in_out[second + n - k]);
         }
 
-        workgroupBarrier();
+        shared_data[idx] = vec2<f32>(a.x - b.y, a.y + b.x); // a + i b
     }
 
     workgroupBarrier();
 
-    for (var i = 0u; i < elements_per_thread; i++) {
-        let idx = thread_id + i * WORKGROUP_SIZE;
-        if (idx < n) {
-            let global_idx = row * n + idx;
-            in_out[global_idx] = shared_data[idx];
+    fft_shared(n, 1u, thread_id, true);
+
+    for (var idx = thread_id; idx < half; idx += WORKGROUP_SIZE) {
+        if (idx < n / 2u) {
+            let even = shared_data[2u * idx];
+            let odd = shared_data[2u * idx + 1u];
+
+            in_out[first + idx] = vec2<f32>(even.x, odd.x);
+            in_out[second + idx] = vec2<f32>(even.y, odd.y);
+        } else {
+            // padding
+            in_out[first + idx] = vec2<f32>(0.0, 0.0);
+            in_out[second + idx] = vec2<f32>(0.0, 0.0);
+        }
+    }
+}
+
+// adjacent columns transformed by one workgroup, so neighbouring threads read and write neighbouring elements of a row
+fn column_tile() -> u32 {
+    return clamp(SHARED_SIZE / uniforms.height, 1u, TILE_SIZE);
+}
+
+// complex transform of a tile of columns of the half spectra, transposed on the way into and out of shared_data
+fn columns(local_id: vec3<u32>, workgroup_id: vec3<u32>, inverse: bool) {
+    let n = uniforms.height;
+    let half = half_size();
+    let thread_id = local_id.x;
+    let count = column_tile();
+
+    let first_column = workgroup_id.x * count;
+    let tile = plane_offset(workgroup_id.y) + first_column;
+
+    // the last tile of a row can stick out of the half spectrum, its extra columns are transformed but never stored
+    for (var e = thread_id; e < count * n; e += WORKGROUP_SIZE) {
+        let column = e % count;
+        let idx = e / count;
+        if (first_column + column < half) {
+            shared_data[column * n + idx] = in_out[tile + column + digit_reverse(idx, n) * half];
+        }
+    }
+
+    workgroupBarrier();
+
+    fft_shared(n, count, thread_id, inverse);
+
+    for (var e = thread_id; e < count * n; e += WORKGROUP_SIZE) {
+        let column = e % count;
+        let idx = e / count;
+        if (first_column + column < half) {
+            in_out[tile + column + idx * half] = shared_data[column * n + idx];
         }
     }
 }
 
 @compute
-// @workgroup_size(1)
 @workgroup_size(256)
-fn fft_forward(
-    @builtin(global_invocation_id) global_id: vec3<u32>,
+fn columns_forward(
     @builtin(local_invocation_id) local_id: vec3<u32>,
     @builtin(workgroup_id) workgroup_id: vec3<u32>,
 ) {
-    fft_1d(global_id, local_id, workgroup_id, false);
+    columns(local_id, workgroup_id, false);
+}
+
+@compute
+@workgroup_size(256)
+fn columns_inverse(
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    columns(local_id, workgroup_id, true);
+}
+
+// Stockham passes for rows and columns longer than SHARED_SIZE. Every pass merges sub-transforms of length `stage.span`
+// into ones `radix` times longer, reading in_out and writing scratch, the caller swaps the buffers between passes.
+// The elements of a line are `stride` apart starting at `line` in in_out, and next to each other starting at `output` in scratch.
+fn stockham_pass(line: u32, stride: u32, output: u32, n: u32, butterfly_id: u32) {
+    let span = stage.span;
+    let radix = next_radix(n / span);
+    if (butterfly_id >= n / radix) {
+        return;
+    }
+
+    let sign = select(1.0, -1.0, stage.inverse != 0u);
+    let j = butterfly_id % span;
+    let block_size = span * radix;
+
+    var inputs: array<vec2<f32>, MAX_RADIX>;
+    for (var q = 0u; q < radix; q++) {
+        let w = twiddle(sign * f32(j * q) / f32(block_size));
+        inputs[q] = complex_mul(w, in_out[line + (butterfly_id + q * (n / radix)) * stride]);
+    }
+
+    let outputs = small_dft(inputs, radix, sign);
+
+    // the merged transforms end up next to each other, so the output is in order after the last pass
+    let start = (butterfly_id / span) * block_size + j;
+    for (var k = 0u; k < radix; k++) {
+        scratch[output + start + k * span] = outputs[k];
+    }
+}
+
+// the pair of rows packed into one complex row lives at `pair * width` of its plane in the stage passes
+@compute
+@workgroup_size(256)
+fn rows_stage(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let row = plane_offset(workgroup_id.z) + workgroup_id.y * uniforms.width;
+    stockham_pass(row, 1u, row, uniforms.width, global_id.x);
 }
 
-@compute 
-// @workgroup_size(1)
+// the columns are transformed as rows of the transposed half spectra, `height` complex numbers each.
+// With `stage.transpose` the pass reads the column out of the half spectra instead, doing the transpose on the way.
+@compute
 @workgroup_size(256)
-fn fft_inverse(
+fn columns_stage(
     @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let plane = plane_offset(workgroup_id.z);
+    let column = plane + workgroup_id.y * uniforms.height;
+
+    if (stage.transpose != 0u) {
+        stockham_pass(plane + workgroup_id.y, half_size(), column, uniforms.height, global_id.x);
+    } else {
+        stockham_pass(column, 1u, column, uniforms.height, global_id.x);
+    }
+}
+
+// out of place transpose of every plane of in_out, a rows x cols matrix, into scratch through transpose_data
+fn transpose_tile(rows: u32, cols: u32, local_id: vec3<u32>, workgroup_id: vec3<u32>) {
+    let plane = plane_offset(workgroup_id.z);
+    let x = workgroup_id.x * TILE_SIZE + local_id.x;
+    let y = workgroup_id.y * TILE_SIZE + local_id.y;
+
+    if (x < cols && y < rows) {
+        transpose_data[local_id.y * (TILE_SIZE + 1u) + local_id.x] = in_out[plane + y * cols + x];
+    }
+
+    workgroupBarrier();
+
+    let transposed_x = workgroup_id.y * TILE_SIZE + local_id.x;
+    let transposed_y = workgroup_id.x * TILE_SIZE + local_id.y;
+    if (transposed_x < rows && transposed_y < cols) {
+        scratch[plane + transposed_y * rows + transposed_x] = transpose_data[local_id.x * (TILE_SIZE + 1u) + local_id.y];
+    }
+}
+
+// half spectra to their columns one after the other
+@compute
+@workgroup_size(16, 16)
+fn transpose_to_columns(
     @builtin(local_invocation_id) local_id: vec3<u32>,
     @builtin(workgroup_id) workgroup_id: vec3<u32>,
 ) {
-    fft_1d(global_id, local_id, workgroup_id, true);
+    transpose_tile(uniforms.height, half_size(), local_id, workgroup_id);
+}
+
+@compute
+@workgroup_size(16, 16)
+fn transpose_to_rows(
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    transpose_tile(half_size(), uniforms.height, local_id, workgroup_id);
+}
+
+// the first half of rows_forward, two real rows of in_out become one complex row of scratch
+@compute
+@workgroup_size(256)
+fn rows_pack(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let n = uniforms.width;
+    let x = global_id.x;
+    if (x >= n) {
+        return;
+    }
+
+    let plane = plane_offset(workgroup_id.z);
+    let first = plane + 2u * workgroup_id.y * half_size();
+
+    scratch[plane + workgroup_id.y * n + x] = vec2<f32>(load_real(first, x), load_real(first + half_size(), x));
+}
+
+// the second half of rows_forward, the transformed complex row in scratch is pulled apart into two half spectra
+@compute
+@workgroup_size(256)
+fn rows_split(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let n = uniforms.width;
+    let half = half_size();
+    let k = global_id.x;
+    if (k >= half) {
+        return;
+    }
+
+    let plane = plane_offset(workgroup_id.z);
+    let row = plane + workgroup_id.y * n;
+    let first = plane + 2u * workgroup_id.y * half;
+
+    let z = scratch[row + k];
+    let mirrored = conj(scratch[row + (n - k) % n]);
+
+    let sum = z + mirrored;
+    let difference = z - mirrored;
+
+    in_out[first + k] = sum * 0.5;
+    in_out[first + half + k] = vec2<f32>(difference.y, -difference.x) * 0.5;
 }
 
+// the first half of rows_inverse, two half spectra of in_out are joined into one complex row of scratch
+@compute
+@workgroup_size(256)
+fn rows_join(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let n = uniforms.width;
+    let half = half_size();
+    let k = global_id.x;
+    if (k >= n) {
+        return;
+    }
+
+    let plane = plane_offset(workgroup_id.z);
+    let first = plane + 2u * workgroup_id.y * half;
+    let second = first + half;
+
+    var a: vec2<f32>;
+    var b: vec2<f32>;
+    if (k < half) {
+        a = in_out[first + k];
+        b = in_out[second + k];
+    } else {
+        a = conj(in_out[first + n - k]);
+        b = conj(in_out[second + n - k]);
+    }
+
+    scratch[plane + workgroup_id.y * n + k] = vec2<f32>(a.x - b.y, a.y + b.x);
+}
+
+// the second half of rows_inverse, the transformed complex row in scratch is written back as two real rows
+@compute
+@workgroup_size(256)
+fn rows_unpack(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let n = uniforms.width;
+    let half = half_size();
+    let idx = global_id.x;
+    if (idx >= half) {
+        return;
+    }
+
+    let plane = plane_offset(workgroup_id.z);
+    let row = plane + workgroup_id.y * n;
+    let first = plane + 2u * workgroup_id.y * half;
+    let second = first + half;
+
+    if (idx < n / 2u) {
+        let even = scratch[row + 2u * idx];
+        let odd = scratch[row + 2u * idx + 1u];
+
+        in_out[first + idx] = vec2<f32>(even.x, odd.x);
+        in_out[second + idx] = vec2<f32>(even.y, odd.y);
+    } else {
+        // padding
+        in_out[first + idx] = vec2<f32>(0.0, 0.0);
+        in_out[second + idx] = vec2<f32>(0.0, 0.0);
+    }
+}
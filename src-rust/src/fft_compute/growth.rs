@@ -1,4 +1,4 @@
-use crate::{storage_manager::Storage, uniforms_manager::Uniforms};
+use crate::{fft_compute::FFTBuffers, storage_manager::Storage, uniforms_manager::Uniforms};
 
 pub struct GrowthState {
     pipeline: wgpu::ComputePipeline,
@@ -7,32 +7,121 @@ pub struct GrowthState {
     pub uniforms: Uniforms<GrowthUniforms>,
 }
 
+/// Maps the neighborhood sum to a growth rate in [-1, 1]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum GrowthFunction {
+    /// exp(-z^2 / 2) * 2 - 1
+    #[default]
+    Gaussian = 0,
+    /// (1 - z^2 / 9)^4 * 2 - 1
+    Polynomial = 1,
+    /// 1 within s of m, -1 elsewhere
+    Rectangular = 2,
+    /// birth within s of m, survival within s of m - 2s, death elsewhere
+    Life = 3,
+}
+
+impl GrowthFunction {
+    /// same mapping as `growth_mapping` in growth.wgsl
+    pub fn eval(self, sum: f32, m: f32, s: f32) -> f32 {
+        let z = (sum - m) / s;
+
+        match self {
+            Self::Gaussian => (-0.5 * z * z).exp() * 2.0 - 1.0,
+            Self::Polynomial => (1.0 - z * z / 9.0).max(0.0).powi(4) * 2.0 - 1.0,
+            Self::Rectangular => if z.abs() <= 1.0 { 1.0 } else { -1.0 },
+            Self::Life => {
+                if z.abs() <= 1.0 {
+                    1.0
+                } else if (z + 2.0).abs() <= 1.0 {
+                    0.0
+                } else {
+                    -1.0
+                }
+            }
+        }
+    }
+}
+
+/// How a cell moves towards its growth during one step of dt = 1 / time_step
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum UpdateRule {
+    /// A + dt * G, clipped to [0, 1]
+    #[default]
+    Euler = 0,
+    /// A + dt * G with the step scaled down smoothly near 0 and 1, see `soft_clip`. 0 and 1 stay fixed points
+    SoftClip = 1,
+    /// A + dt * (G' - A) with G' the growth mapped to a target in [0, 1]
+    Asymptotic = 2,
+    /// Euler, then every channel is rescaled to the mass it had before the step, as far as the clip at 1 allows
+    MassConserving = 3,
+}
+
+impl UpdateRule {
+    /// same update as growth.wgsl, `MassConserving` is rescaled afterwards like conserve.wgsl
+    pub fn apply(self, old: f32, growth: f32, dt: f32) -> f32 {
+        match self {
+            Self::Euler | Self::MassConserving => (old + dt * growth).clamp(0.0, 1.0),
+            Self::SoftClip => (old + soft_clip(old + dt * growth) - soft_clip(old)).clamp(0.0, 1.0),
+            Self::Asymptotic => {
+                let goal = ((growth + 1.0) / 2.0).clamp(0.0, 1.0);
+                (old + dt * (goal - old)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// sharpness of `soft_clip`, the step is halved at the bounds and almost untouched in the middle
+const SOFT_CLIP_SHARPNESS: f32 = 10.0;
+
+/// log(1 + exp(z)) without overflowing for large z
+fn softplus(z: f32) -> f32 {
+    z.max(0.0) + (1.0 + (-z.abs()).exp()).ln()
+}
+
+/// 1/k ln((1 + exp(kx)) / (1 + exp(k(x - 1)))), a smooth clamp to [0, 1]. Applying it to A + dt * G directly would
+/// move 0 and 1 (it is ln(2)/k at 0), so `UpdateRule::SoftClip` only uses it for the size of the step
+fn soft_clip(x: f32) -> f32 {
+    let k = SOFT_CLIP_SHARPNESS;
+    (softplus(k * x) - softplus(k * (x - 1.0))) / k
+}
+
 #[derive(Clone, Copy, Debug, encase::ShaderType)]
 pub struct GrowthUniforms {
-    pub time_step: u32,
-    pub m: f32,
-    pub s: f32,
-    pub fft_size: u32,
+    /// steps per unit of time, dt is its inverse
+    pub time_step: f32,
+    pub update_rule: u32,
+    pub growth_function: u32,
+    pub fft_width: u32,
+    pub fft_height: u32,
     pub height: u32,
     pub width: u32,
+    pub channels: u32,
+    pub kernels: u32,
 }
 
 impl GrowthState {
-    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, grid: &Storage, fft_buffer: &Storage, uniforms: &Uniforms<GrowthUniforms>) -> wgpu::BindGroup {
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, grid: &Storage, buffers: &FFTBuffers, uniforms: &Uniforms<GrowthUniforms>) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor { 
             label: Some("Growth Bind Group"), 
             layout, 
             entries: &[
                 uniforms.bind_group_entry(0),
-                fft_buffer.bind_group_entry(1),
+                buffers.potential.bind_group_entry(1),
                 grid.bind_group_entry(2),
+                buffers.kernel_params.bind_group_entry(3),
+                buffers.fields.bind_group_entry(4),
             ] 
         })
     }
 
     pub fn new(
         device: &wgpu::Device, 
-        fft_buffer: &Storage, 
+        buffers: &FFTBuffers, 
         grid: &Storage,
         uniforms: GrowthUniforms
     ) -> Self {
@@ -44,12 +133,14 @@ impl GrowthState {
             label: Some("Growth Bind Group Layout"), 
             entries: &[
                 uniforms.layout_entry(0, wgpu::ShaderStages::COMPUTE),
-                fft_buffer.layout_entry(1, wgpu::ShaderStages::COMPUTE, true),
-                grid.layout_entry(2, wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT, false)
+                buffers.potential.layout_entry(1, wgpu::ShaderStages::COMPUTE, true),
+                grid.layout_entry(2, wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT, false),
+                buffers.kernel_params.layout_entry(3, wgpu::ShaderStages::COMPUTE, true),
+                buffers.fields.layout_entry(4, wgpu::ShaderStages::COMPUTE, false),
             ] 
         });
 
-        let bind_group = Self::create_bind_group(device, &bind_group_layout, grid, fft_buffer, &uniforms);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, grid, buffers, &uniforms);
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { 
             label: Some("Growth Pipeline Layout"), 
@@ -78,9 +169,9 @@ impl GrowthState {
         &mut self,
         device: &wgpu::Device
         , grid: &Storage,
-        fft_buffer: &Storage
+        buffers: &FFTBuffers
     ) {
-        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, grid, fft_buffer, &self.uniforms);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, grid, buffers, &self.uniforms);
     }
 
     pub fn run(
@@ -98,8 +189,39 @@ impl GrowthState {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
 
-        let workgroups_x = (self.uniforms.width + 15) / 16;
-        let workgroups_y = (self.uniforms.height + 15) / 16;
-        pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+        let workgroups_x = self.uniforms.width.div_ceil(16);
+        let workgroups_y = self.uniforms.height.div_ceil(16);
+        pass.dispatch_workgroups(workgroups_x, workgroups_y, self.uniforms.channels);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soft_clip_keeps_the_bounds_and_still_cells() {
+        let rule = UpdateRule::SoftClip;
+
+        for time_step in [0.5, 10.0, 50.0] {
+            let dt = 1.0 / time_step;
+            for growth in [-1.0, -0.3, 0.0] {
+                assert_eq!(rule.apply(0.0, growth, dt), 0.0, "T {} G {}", time_step, growth);
+            }
+            for growth in [0.0, 0.3, 1.0] {
+                assert_eq!(rule.apply(1.0, growth, dt), 1.0, "T {} G {}", time_step, growth);
+            }
+            for old in [0.01, 0.3, 0.5, 0.99] {
+                assert!((rule.apply(old, 0.0, dt) - old).abs() < 1e-6);
+
+                // slower than euler near the bounds, but in the same direction
+                let up = rule.apply(old, 1.0, dt);
+                let down = rule.apply(old, -1.0, dt);
+                assert!(old < up && up <= UpdateRule::Euler.apply(old, 1.0, dt), "T {} A {}: {}", time_step, old, up);
+                assert!(old > down && down >= UpdateRule::Euler.apply(old, -1.0, dt), "T {} A {}: {}", time_step, old, down);
+            }
+        }
+
+        // almost euler away from the bounds
+        assert!((rule.apply(0.5, 1.0, 0.02) - 0.52).abs() < 1e-3);
+    }
+}
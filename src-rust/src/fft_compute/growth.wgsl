@@ -1,15 +1,94 @@
 struct GrowthUniforms {
-    time_step: u32,
-    m: f32,
-    s: f32,
-    fft_size: u32,
+    time_step: f32,
+    update_rule: u32,
+    growth_function: u32,
+    fft_width: u32,
+    fft_height: u32,
     height: u32,
     width: u32,
+    channels: u32,
+    kernels: u32,
+}
+
+struct KernelParams {
+    source_channel: u32,
+    target_channel: u32,
+    m: f32,
+    s: f32,
+    weight: f32,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: GrowthUniforms;
-@group(0) @binding(1) var<storage, read> neighbors_sum: array<vec2<f32>>;
+// real cells, see fft.wgsl for the padding of the rows
+@group(0) @binding(1) var<storage, read> neighbors_sum: array<f32>;
 @group(0) @binding(2) var<storage, read_write> in_out: array<f32>;
+@group(0) @binding(3) var<storage, read> params: array<KernelParams>;
+// growth rate and change of the cell, kept around for the render pass
+@group(0) @binding(4) var<storage, read_write> fields: array<vec2<f32>>;
+
+const GROWTH_GAUSSIAN: u32 = 0u;
+const GROWTH_POLYNOMIAL: u32 = 1u;
+const GROWTH_RECTANGULAR: u32 = 2u;
+const GROWTH_LIFE: u32 = 3u;
+
+const UPDATE_EULER: u32 = 0u;
+const UPDATE_SOFT_CLIP: u32 = 1u;
+const UPDATE_ASYMPTOTIC: u32 = 2u;
+// euler here, conserve.wgsl rescales the channel afterwards
+const UPDATE_MASS_CONSERVING: u32 = 3u;
+
+// maps a neighborhood sum to a growth rate in [-1, 1]
+fn growth_mapping(sum: f32, m: f32, s: f32, function: u32) -> f32 {
+    let z = (sum - m) / s;
+
+    switch function {
+        case GROWTH_POLYNOMIAL: {
+            return pow(max(1.0 - z * z / 9.0, 0.0), 4.0) * 2.0 - 1.0;
+        }
+        case GROWTH_RECTANGULAR: {
+            return select(-1.0, 1.0, abs(z) <= 1.0);
+        }
+        case GROWTH_LIFE: {
+            // birth band centered on m, survival band right below it
+            if (abs(z) <= 1.0) {
+                return 1.0;
+            }
+            return select(-1.0, 0.0, abs(z + 2.0) <= 1.0);
+        }
+        default: {
+            return exp(-0.5 * z * z) * 2.0 - 1.0;
+        }
+    }
+}
+
+const SOFT_CLIP_SHARPNESS: f32 = 10.0;
+
+// log(1 + exp(z)) without overflowing for large z
+fn softplus(z: f32) -> f32 {
+    return max(z, 0.0) + log(1.0 + exp(-abs(z)));
+}
+
+// soft_clip in growth.rs
+fn soft_clip(x: f32) -> f32 {
+    let k = SOFT_CLIP_SHARPNESS;
+    return (softplus(k * x) - softplus(k * (x - 1.0))) / k;
+}
+
+// UpdateRule::apply in growth.rs
+fn update(old: f32, growth: f32, dt: f32) -> f32 {
+    switch uniforms.update_rule {
+        case UPDATE_SOFT_CLIP: {
+            return clamp(old + soft_clip(old + dt * growth) - soft_clip(old), 0.0, 1.0);
+        }
+        case UPDATE_ASYMPTOTIC: {
+            let goal = clamp((growth + 1.0) / 2.0, 0.0, 1.0);
+            return clamp(old + dt * (goal - old), 0.0, 1.0);
+        }
+        default: {
+            return clamp(old + dt * growth, 0.0, 1.0);
+        }
+    }
+}
 
 @compute
 @workgroup_size(16, 16)
@@ -17,21 +96,34 @@ fn growth(
     @builtin(global_invocation_id) global_id: vec3<u32>,
     @builtin(local_invocation_id) local_id: vec3<u32>,
 ) {
-    let fft_size = uniforms.fft_size;
     let width = uniforms.width;
     let height = uniforms.height;
 
     let x = global_id.x;
     let y = global_id.y;
+    let channel = global_id.z;
 
-    if(x >= width || y >= height) {
+    if(x >= width || y >= height || channel >= uniforms.channels) {
         return;
     }
 
-    let sum = neighbors_sum[y * fft_size + x].x / f32(fft_size * fft_size);
-    // in_out[y * width + x] = sum;
-    let z = (sum - uniforms.m) / uniforms.s;
-    let growth = exp(-0.5 * z * z) * 2.0 - 1.0;
+    let stride = 2u * (uniforms.fft_width / 2u + 1u);
+
+    // sum the weighted growth of every kernel targeting this channel
+    var growth = 0.0;
+    for (var k = 0u; k < uniforms.kernels; k++) {
+        if (params[k].target_channel != channel) {
+            continue;
+        }
+
+        let sum = neighbors_sum[k * uniforms.fft_height * stride + y * stride + x] / f32(uniforms.fft_width * uniforms.fft_height);
+        growth += params[k].weight * growth_mapping(sum, params[k].m, params[k].s, uniforms.growth_function);
+    }
+
+    let idx = channel * width * height + y * width + x;
+    let old = in_out[idx];
+    let updated = update(old, growth, 1.0 / uniforms.time_step);
+    in_out[idx] = updated;
 
-    in_out[y * width + x] = clamp(in_out[y * width + x] + (1.0/f32(uniforms.time_step)) * growth, 0.0, 1.0);
+    fields[channel * uniforms.fft_width * uniforms.fft_height + y * uniforms.fft_width + x] = vec2<f32>(growth, updated - old);
 }
@@ -1,60 +1,136 @@
-use crate::{compute::generate_kernel, fft_compute::{FFTState, TransposeState}, storage_manager::Storage, uniforms_manager::Uniforms};
+use crate::{fft_compute::{FFTBuffers, FFTState, FFTUniforms}, kernel_description::KernelDescription, storage_manager::Storage, uniforms_manager::Uniforms};
 
 pub struct KernelState {
     pipeline: wgpu::ComputePipeline,
     bind_group: wgpu::BindGroup,
     bind_group_layout: wgpu::BindGroupLayout,
-    kernel_radius: u32,
+    kernel: Storage,
+    descriptions: Vec<KernelDescription>,
+    cache: KernelCache,
     pub uniforms: Uniforms<KernelUniforms>
 }
 
+/// spectra of the kernels transformed so far, keyed by description and fft size, least recently used first.
+/// Has its own fft, so filling it never touches the bind groups of the simulation.
+#[derive(Default)]
+pub struct KernelCache {
+    fft: Option<FFTState>,
+    spectra: Vec<CachedSpectrum>,
+}
+
+struct CachedSpectrum {
+    description: KernelDescription,
+    size: (u32, u32),
+    spectrum: Storage,
+}
+
+impl KernelCache {
+    /// spectra beyond this many bytes are dropped, oldest first, as long as they aren't in use
+    const MAX_BYTES: u64 = 256 << 20;
+
+    /// index of the half spectrum of `description` on width x height planes, transformed on a miss
+    fn spectrum(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        description: &KernelDescription,
+        (width, height): (u32, u32),
+    ) -> usize {
+        if let Some(index) = self.spectra.iter().position(|c| c.size == (width, height) && c.description == *description) {
+            let cached = self.spectra.remove(index);
+            self.spectra.push(cached);
+            return self.spectra.len() - 1;
+        }
+
+        // real cells, every row padded to the stride of the fft planes
+        let stride = FFTUniforms::real_stride(width) as usize;
+        let mut plane = vec![0f32; height as usize * stride];
+        for (row, cells) in plane.chunks_exact_mut(stride).zip(description.wrapped(width, height).chunks_exact(width as usize)) {
+            row[..cells.len()].copy_from_slice(cells);
+        }
+
+        let spectrum = Storage::new(device, "Kernel Spectrum", &plane);
+        let uniforms = FFTUniforms { width, height, planes: 1 };
+
+        // the uniforms of an fft are written before the encoder runs, so every size gets its own
+        let fft = match &mut self.fft {
+            Some(fft) if fft.uniforms.width == width && fft.uniforms.height == height => {
+                fft.recreate_bind_groups(device, &spectrum);
+                fft
+            }
+            _ => self.fft.insert(FFTState::new(device, &spectrum, uniforms)),
+        };
+        fft.run_forward(encoder, queue);
+
+        self.spectra.push(CachedSpectrum {
+            description: description.clone(),
+            size: (width, height),
+            spectrum,
+        });
+        self.spectra.len() - 1
+    }
+
+    /// drops the oldest spectra over `MAX_BYTES`, keeping the last `in_use`
+    fn trim(&mut self, in_use: usize) {
+        let mut bytes: u64 = self.spectra.iter().map(|c| c.spectrum.buffer().size()).sum();
+        while bytes > Self::MAX_BYTES && self.spectra.len() > in_use {
+            bytes -= self.spectra.remove(0).spectrum.buffer().size();
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, encase::ShaderType)]
 pub struct KernelUniforms {
-    pub size: u32,
+    /// size of the fft planes
+    pub width: u32,
+    pub height: u32,
+    pub kernels: u32,
 }
 
 impl KernelState {
+    /// `cache` can hold the spectra of a previous `KernelState`
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        device: &wgpu::Device, 
-        fft_buffer: &Storage, 
+        device: &wgpu::Device,
+        buffers: &FFTBuffers,
         encoder: &mut wgpu::CommandEncoder,
         queue: &wgpu::Queue,
-        kernel_radius: u32,
-        height: u32,
-        width: u32,
-        fft: &mut FFTState,
-        transpose: &mut TransposeState,
+        descriptions: Vec<KernelDescription>,
+        mut cache: KernelCache,
         uniforms: KernelUniforms,
     ) -> Self {
         let shader = device.create_shader_module(wgpu::include_wgsl!("kernel.wgsl"));
 
-        let kernel = Self::create_kernel_buffer(device, encoder, queue, uniforms.size, kernel_radius, height, width, fft_buffer, fft, transpose);
+        let kernel = Self::create_kernel_buffer(device, encoder, queue, &uniforms, &descriptions, &mut cache);
 
         let uniforms = Uniforms::new(device, "Kernel", uniforms);
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { 
-            label: Some("Kernel Bind Group Layout"), 
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Kernel Bind Group Layout"),
             entries: &[
                 uniforms.layout_entry(0, wgpu::ShaderStages::COMPUTE),
-                fft_buffer.layout_entry(1, wgpu::ShaderStages::COMPUTE,  false),
+                buffers.spectra.layout_entry(1, wgpu::ShaderStages::COMPUTE, true),
                 kernel.layout_entry(2, wgpu::ShaderStages::COMPUTE, true),
+                buffers.potential.layout_entry(3, wgpu::ShaderStages::COMPUTE, false),
+                buffers.kernel_params.layout_entry(4, wgpu::ShaderStages::COMPUTE, true),
             ]
         });
 
-        let bind_group = Self::create_bind_group(device, &bind_group_layout, &uniforms, fft_buffer, &kernel);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &uniforms, buffers, &kernel);
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { 
-            label: Some("Kernel Pipeline Layout"), 
-            bind_group_layouts: &[&bind_group_layout], 
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Kernel Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
             push_constant_ranges: &[]
         });
 
-        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor { 
-            label: Some("Kernel Pipeline"), 
-            layout: Some(&pipeline_layout), 
-            module: &shader, 
-            entry_point: None, 
-            compilation_options: Default::default(), 
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Kernel Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: None,
+            compilation_options: Default::default(),
             cache: None
         });
 
@@ -62,89 +138,88 @@ impl KernelState {
             pipeline,
             bind_group,
             bind_group_layout,
+            kernel,
             uniforms,
-            kernel_radius,
+            descriptions,
+            cache,
         }
     }
 
+    /// copies the fft of every kernel out of the cache, one plane per kernel, in the same order as the potential buffer
     fn create_kernel_buffer(
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
         queue: &wgpu::Queue,
-        fft_size: u32,
-        kernel_radius: u32,
-        height: u32,
-        width: u32,
-        fft_buffer: &Storage,
-        fft: &mut FFTState,
-        transpose: &mut TransposeState,
+        uniforms: &KernelUniforms,
+        descriptions: &[KernelDescription],
+        cache: &mut KernelCache,
     ) -> Storage {
-        let kernel_data = generate_kernel(kernel_radius);
-        let mut padded_kernel = vec![vec![[0f32; 2]; fft_size as usize]; fft_size as usize];
-
-        let kernel_sum: f32 = kernel_data.iter().map(|r| r.iter().sum::<f32>()).sum();
-
-        let kernel_size: usize = (kernel_radius * 2 + 1) as usize;
-        
-        for i in 0..kernel_size {
-            for j in 0..kernel_size {
-                let pi = (i as i32 - kernel_radius as i32).rem_euclid(fft_size as i32) as usize; 
-                let pj = (j as i32 - kernel_radius as i32).rem_euclid(fft_size as i32) as usize; 
-                padded_kernel[pi][pj]=[kernel_data[i][j] / kernel_sum, 0.0];
-            }
-        }
+        let plane_bytes = FFTUniforms::plane_bytes(uniforms.width, uniforms.height);
+        let kernel_buffer = Storage::new_empty(device, "Kernel", plane_bytes * descriptions.len() as u64);
 
-        let flattened_kernel = padded_kernel.into_iter().flatten().collect::<Vec<_>>();
-        let kernel_buffer = Storage::new(device, "Kernel", &flattened_kernel);
-
-        // run fft on the kernel
-        fft.recreate_bind_groups(device, &kernel_buffer);
-        transpose.recreate_bind_groups(device, &kernel_buffer);
-
-        fft.run_forward(encoder, queue);
-        transpose.run(encoder, queue);
-        fft.run_forward(encoder, queue);
+        for (i, description) in descriptions.iter().enumerate() {
+            let index = cache.spectrum(device, encoder, queue, description, (uniforms.width, uniforms.height));
+            encoder.copy_buffer_to_buffer(cache.spectra[index].spectrum.buffer(), 0, kernel_buffer.buffer(), i as u64 * plane_bytes, plane_bytes);
+        }
 
-        // reset bind groups
-        fft.recreate_bind_groups(device, &fft_buffer);
-        transpose.recreate_bind_groups(device, &fft_buffer);
+        cache.trim(descriptions.len());
 
         kernel_buffer
     }
 
-
     fn create_bind_group(
         device: &wgpu::Device,
         bind_group_layout: &wgpu::BindGroupLayout,
         uniforms: &Uniforms<KernelUniforms>,
-        fft_buffer: &Storage,
+        buffers: &FFTBuffers,
         kernel: &Storage,
     ) -> wgpu::BindGroup {
-        device.create_bind_group(&wgpu::BindGroupDescriptor { 
-            label: Some("Kernel Bind Group"), 
-            layout: &bind_group_layout, 
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Kernel Bind Group"),
+            layout: bind_group_layout,
             entries: &[
                 uniforms.bind_group_entry(0),
-                fft_buffer.bind_group_entry(1),
+                buffers.spectra.bind_group_entry(1),
                 kernel.bind_group_entry(2),
-            ] 
+                buffers.potential.bind_group_entry(3),
+                buffers.kernel_params.bind_group_entry(4),
+            ]
         })
     }
-    
+
+    /// regenerates the kernel buffer for the current fft size, kernels seen before at this size come from the cache
     pub fn recreate_bind_groups(
         &mut self,
         device: &wgpu::Device,
-        fft_buffer: &Storage,
+        buffers: &FFTBuffers,
         encoder: &mut wgpu::CommandEncoder,
         queue: &wgpu::Queue,
-        fft_size: u32,
-        height: u32,
-        width: u32,
-        fft: &mut FFTState,
-        transpose: &mut TransposeState
     ) {
-        let kernel = Self::create_kernel_buffer(device, encoder, queue, fft_size, self.kernel_radius, height, width, fft_buffer, fft, transpose);
-        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.uniforms, fft_buffer, &kernel);
+        self.kernel = Self::create_kernel_buffer(device, encoder, queue, &self.uniforms, &self.descriptions, &mut self.cache);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.uniforms, buffers, &self.kernel);
+    }
+
+    pub fn descriptions(&self) -> &[KernelDescription] {
+        &self.descriptions
+    }
+
+    /// swaps the kernels, only the ones missing from the cache are re-FFT'd. The fft buffers are left untouched
+    pub fn set_descriptions(
+        &mut self,
+        device: &wgpu::Device,
+        buffers: &FFTBuffers,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        descriptions: Vec<KernelDescription>,
+    ) {
+        self.descriptions = descriptions;
+        self.uniforms.kernels = self.descriptions.len() as u32;
+        self.recreate_bind_groups(device, buffers, encoder, queue);
+    }
+
+    /// hands the cached spectra over to the next `KernelState`
+    pub fn take_cache(&mut self) -> KernelCache {
+        std::mem::take(&mut self.cache)
     }
 
     pub fn run(
@@ -159,7 +234,10 @@ impl KernelState {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
 
-        let groups = (self.uniforms.size + 15) / 16;
-        pass.dispatch_workgroups(groups, groups, 1);
+        pass.dispatch_workgroups(
+            FFTUniforms::half_size(self.uniforms.width).div_ceil(16),
+            self.uniforms.height.div_ceil(16),
+            self.uniforms.kernels,
+        );
     }
-}
\ No newline at end of file
+}
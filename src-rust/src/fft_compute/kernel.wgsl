@@ -1,10 +1,22 @@
 struct KernelUniforms {
-    size: u32,
+    width: u32,
+    height: u32,
+    kernels: u32,
+}
+
+struct KernelParams {
+    source_channel: u32,
+    target_channel: u32,
+    m: f32,
+    s: f32,
+    weight: f32,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: KernelUniforms;
-@group(0) @binding(1) var<storage, read_write> in_out: array<vec2<f32>>;
+@group(0) @binding(1) var<storage, read> spectra: array<vec2<f32>>;
 @group(0) @binding(2) var<storage, read> kernel: array<vec2<f32>>;
+@group(0) @binding(3) var<storage, read_write> potential: array<vec2<f32>>;
+@group(0) @binding(4) var<storage, read> params: array<KernelParams>;
 
 fn complex_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
     return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
@@ -17,12 +29,21 @@ fn apply_kernel(
 ) {
     let x = global_id.x;
     let y = global_id.y;
-    
-    if (x >= uniforms.size || y >= uniforms.size) {
+    let k = global_id.z;
+
+    // the planes hold the half spectra of their rows, see fft.wgsl
+    let half = uniforms.width / 2u + 1u;
+
+    if (x >= half || y >= uniforms.height || k >= uniforms.kernels) {
         return;
     }
-    
-    let idx = y * uniforms.size + x;
 
-    in_out[idx] = complex_mul(in_out[idx], kernel[idx]);
-}
\ No newline at end of file
+    let plane_size = uniforms.height * half;
+    let idx = y * half + x;
+
+    // each kernel convolves its source channel into its own potential plane
+    let source_idx = params[k].source_channel * plane_size + idx;
+    let kernel_idx = k * plane_size + idx;
+
+    potential[kernel_idx] = complex_mul(spectra[source_idx], kernel[kernel_idx]);
+}
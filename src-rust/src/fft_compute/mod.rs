@@ -1,22 +1,74 @@
-pub use crate::{fft_compute::{fft::{FFTState, FFTUniforms}, growth::{GrowthState, GrowthUniforms}, kernel::{KernelState, KernelUniforms}, pad_wrap::{PadWrapState, PadWrapUniforms}, transpose::{TransposeState, TransposeUniforms}}, storage_manager::Storage};
+use anyhow::anyhow;
+
+pub use crate::{fft_compute::{conserve::{ConserveState, ConserveUniforms}, fft::{FFTState, FFTUniforms}, growth::{GrowthFunction, GrowthState, GrowthUniforms, UpdateRule}, kernel::{KernelCache, KernelState, KernelUniforms}, pad_wrap::{BoundaryMode, PadWrapState, PadWrapUniforms}}, kernel_description::KernelDescription, simulation::{ChannelKernel, SimulationConfig}, storage_manager::Storage};
 
 mod pad_wrap;
 mod fft;
-mod transpose;
 mod kernel;
 mod growth;
+mod conserve;
+
+/// Per kernel values read by the kernel and growth passes, matches `KernelParams` in the shaders
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct KernelParams {
+    pub source: u32,
+    pub target: u32,
+    pub m: f32,
+    pub s: f32,
+    pub weight: f32,
+}
 
+impl From<&ChannelKernel> for KernelParams {
+    fn from(kernel: &ChannelKernel) -> Self {
+        Self {
+            source: kernel.source,
+            target: kernel.target,
+            m: kernel.m,
+            s: kernel.s,
+            weight: kernel.weight,
+        }
+    }
+}
+
+pub struct FFTBuffers {
+    /// one fft plane per channel, see fft.wgsl for the layout
+    pub spectra: Storage,
+    /// one fft plane per kernel, holds the convolution of the kernel with its source channel as real cells
+    pub potential: Storage,
+    /// one fft plane per channel, growth rate and change of each cell during the last step
+    pub fields: Storage,
+    pub kernel_params: Storage,
+}
+
+impl FFTBuffers {
+    fn new(device: &wgpu::Device, (fft_width, fft_height): (u32, u32), config: &SimulationConfig) -> Self {
+        let plane_size = (fft_width * fft_height * 4 * 2) as u64;
+        let fft_plane_size = FFTUniforms::plane_bytes(fft_width, fft_height);
+        let kernel_params = config.kernels.iter().map(KernelParams::from).collect::<Vec<_>>();
+
+        Self {
+            spectra: Storage::new_empty(device, "FFT", fft_plane_size * config.channels as u64),
+            potential: Storage::new_empty(device, "Potential", fft_plane_size * config.kernels.len() as u64),
+            fields: Storage::new_empty(device, "Growth Fields", plane_size * config.channels as u64),
+            kernel_params: Storage::new(device, "Kernel Parameters", &kernel_params),
+        }
+    }
+}
 
 pub struct FFTComputeState {
     fft: FFTState,
     pad_wrap: PadWrapState,
-    transpose: TransposeState,
+    potential_fft: FFTState,
     kernel: KernelState,
     growth: GrowthState,
-    fft_buffer: Storage
+    conserve: ConserveState,
+    buffers: FFTBuffers,
+    config: SimulationConfig,
 }
 
 impl FFTComputeState {
+    /// `grid` has to hold `config.channels` planes of width x height cells
     pub fn new(
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
@@ -24,53 +76,111 @@ impl FFTComputeState {
         grid: &Storage,
         width: u32,
         height: u32,
+        config: SimulationConfig,
     ) -> Self {
-        let kernel_radius= 0;
+        Self::with_kernel_cache(device, encoder, queue, grid, width, height, config, KernelCache::default())
+    }
 
-        let (fft_size, fft_buffer) = Self::create_fft_buffer(device, width, height, kernel_radius);
+    /// same as `new`, for a config that changes the number of channels or kernels.
+    /// Kernels transformed by the old state are taken from its cache.
+    #[allow(clippy::too_many_arguments)]
+    pub fn rebuild(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        grid: &Storage,
+        width: u32,
+        height: u32,
+        config: SimulationConfig,
+    ) {
+        let cache = self.kernel.take_cache();
+        *self = Self::with_kernel_cache(device, encoder, queue, grid, width, height, config, cache);
+    }
 
-        let pad_wrap = PadWrapState::new(device, grid, &fft_buffer, PadWrapUniforms {
-            width, height, size: fft_size
+    #[allow(clippy::too_many_arguments)]
+    fn with_kernel_cache(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        grid: &Storage,
+        width: u32,
+        height: u32,
+        config: SimulationConfig,
+        cache: KernelCache,
+    ) -> Self {
+        let kernel_radius = config.max_radius();
+        let channels = config.channels;
+        let kernels = config.kernels.len() as u32;
+
+        let (fft_width, fft_height) = Self::fft_size(width, height, kernel_radius);
+        let buffers = FFTBuffers::new(device, (fft_width, fft_height), &config);
+
+        let pad_wrap = PadWrapState::new(device, grid, &buffers.spectra, PadWrapUniforms {
+            width,
+            height,
+            fft_width,
+            fft_height,
+            channels,
+            radius: kernel_radius,
+            boundary: config.boundary as u32,
         });
 
-        let mut fft = FFTState::new(device, &fft_buffer, FFTUniforms {
-            size: fft_size,
-            num_stages: fft_size.ilog2(),
+        let fft = FFTState::new(device, &buffers.spectra, FFTUniforms {
+            width: fft_width,
+            height: fft_height,
+            planes: channels,
         });
 
-        let mut transpose = TransposeState::new(device, &fft_buffer, TransposeUniforms { size: fft_size });
+        let potential_fft = FFTState::new(device, &buffers.potential, FFTUniforms {
+            width: fft_width,
+            height: fft_height,
+            planes: kernels,
+        });
 
         let kernel = KernelState::new(
-            device, 
-            &fft_buffer, 
+            device,
+            &buffers,
             encoder,
             queue,
-            40,
-            height,
-            width,
-            &mut fft,
-            &mut transpose, 
+            config.kernels.iter().map(|k| k.kernel.clone()).collect(),
+            cache,
             KernelUniforms {
-                size: fft_size,
+                width: fft_width,
+                height: fft_height,
+                kernels,
             }
         );
 
-        let growth = GrowthState::new(device, &fft_buffer, grid, GrowthUniforms {
-            fft_size,
-            time_step: 50,
-            m: 0.135,
-            s: 0.015,
+        let growth = GrowthState::new(device, &buffers, grid, GrowthUniforms {
+            fft_width,
+            fft_height,
+            time_step: config.time_step,
+            update_rule: config.update_rule as u32,
+            growth_function: config.growth_function as u32,
             height,
             width,
+            channels,
+            kernels,
+        });
+
+        let conserve = ConserveState::new(device, &buffers, grid, ConserveUniforms {
+            fft_width,
+            fft_height,
+            height,
+            width,
+            channels,
         });
 
         Self {
             fft,
             pad_wrap,
-            transpose,
+            potential_fft,
             kernel,
             growth,
-            fft_buffer,
+            conserve,
+            buffers,
+            config,
         }
     }
 
@@ -81,72 +191,197 @@ impl FFTComputeState {
     ) {
         self.pad_wrap.run(encoder, queue);
         self.fft.run_forward(encoder, queue);
-        self.transpose.run(encoder, queue);
-        self.fft.run_forward(encoder, queue);
         self.kernel.run(encoder, queue);
-        
-        self.fft.run_inverse(encoder, queue);
-        self.transpose.run(encoder, queue);
-        self.fft.run_inverse(encoder, queue);
+        self.potential_fft.run_inverse(encoder, queue);
+
+        let conserve = self.config.update_rule == UpdateRule::MassConserving;
+        if conserve {
+            self.conserve.run_before(encoder, queue);
+        }
+
         self.growth.run(encoder, queue);
+
+        if conserve {
+            self.conserve.run_after(encoder);
+        }
     }
 
-    pub fn create_fft_buffer(
-        device: &wgpu::Device,
+    /// smallest width and height of a plane that fits the grid plus `kernel_radius` ghost cells on every side
+    pub fn fft_size(
         width: u32,
         height: u32,
         kernel_radius: u32
-    ) -> (u32, Storage) {
-        let max_dim = width.max(height);
-        let fft_size = (max_dim + 2 * kernel_radius).next_power_of_two().max(256);
+    ) -> (u32, u32) {
+        (
+            FFTUniforms::padded_len(width + 2 * kernel_radius),
+            FFTUniforms::padded_len(height + 2 * kernel_radius),
+        )
+    }
 
-        (fft_size, Storage::new_empty(device, "FFT", (fft_size.pow( 2) * 4 * 2) as u64))
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    /// replaced on resize and when a kernel outgrows the planes, bind groups using them have to be recreated
+    pub fn buffers(&self) -> &FFTBuffers {
+        &self.buffers
+    }
+
+    /// width and height of the fft planes in use
+    pub fn size(&self) -> (u32, u32) {
+        (self.pad_wrap.uniforms.fft_width, self.pad_wrap.uniforms.fft_height)
     }
 
     pub fn handle_resize(
-        &mut self, 
-        device: &wgpu::Device, 
-        encoder: &mut wgpu::CommandEncoder, 
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
         queue: &wgpu::Queue,
-        grid: &Storage, 
+        grid: &Storage,
         height: u32,
         width: u32
     ) {
-        let max_dim = width.max(height);
-        let (fft_size, fft_buffer) = Self::create_fft_buffer(device, width, height, 0);
+        let kernel_radius = self.config.max_radius();
+        let (fft_width, fft_height) = Self::fft_size(width, height, kernel_radius);
+        let buffers = FFTBuffers::new(device, (fft_width, fft_height), &self.config);
 
-        log::info!("height: {}, width: {}, -> max_dim: {} -> FFT buffer size: {}, {} stages", height, width, max_dim, fft_size, fft_size.ilog2());
+        log::info!("height: {}, width: {}, kernel radius: {} -> FFT buffer size: {}x{}", height, width, kernel_radius, fft_width, fft_height);
 
         // this should probably be handled better... easy to leave stuff out
         self.pad_wrap.uniforms.height = height;
         self.pad_wrap.uniforms.width = width;
-        self.pad_wrap.uniforms.size = fft_size;
-        self.fft.uniforms.size = fft_size;
-        self.fft.uniforms.num_stages = fft_size.ilog2();
-        self.transpose.uniforms.size = fft_size;
-        self.kernel.uniforms.size = fft_size;
-        self.growth.uniforms.fft_size = fft_size;
+        self.pad_wrap.uniforms.fft_width = fft_width;
+        self.pad_wrap.uniforms.fft_height = fft_height;
+        self.pad_wrap.uniforms.radius = kernel_radius;
+        self.fft.uniforms.width = fft_width;
+        self.fft.uniforms.height = fft_height;
+        self.potential_fft.uniforms.width = fft_width;
+        self.potential_fft.uniforms.height = fft_height;
+        self.kernel.uniforms.width = fft_width;
+        self.kernel.uniforms.height = fft_height;
+        self.growth.uniforms.fft_width = fft_width;
+        self.growth.uniforms.fft_height = fft_height;
         self.growth.uniforms.width = width;
         self.growth.uniforms.height = height;
+        self.conserve.uniforms.fft_width = fft_width;
+        self.conserve.uniforms.fft_height = fft_height;
+        self.conserve.uniforms.width = width;
+        self.conserve.uniforms.height = height;
+
+        self.pad_wrap.recreate_bind_groups(device, grid, &buffers.spectra);
+        self.fft.recreate_bind_groups(device, &buffers.spectra);
+        self.potential_fft.recreate_bind_groups(device, &buffers.potential);
+        self.growth.recreate_bind_groups(device, grid, &buffers);
+        self.conserve.recreate_bind_groups(device, grid, &buffers);
+
+        // picks up kernels changed through `set_kernel` or `update` as well
+        self.kernel.set_descriptions(
+            device,
+            &buffers,
+            encoder,
+            queue,
+            self.config.kernels.iter().map(|k| k.kernel.clone()).collect(),
+        );
 
+        self.buffers = buffers;
+    }
+
+    /// swaps the shape of one kernel, its source, target and growth parameters stay the same.
+    /// The fft buffers are resized if the new radius doesn't fit in them anymore.
+    pub fn set_kernel(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        grid: &Storage,
+        index: usize,
+        kernel: KernelDescription,
+    ) -> anyhow::Result<()> {
+        kernel.validate()?;
+
+        let kernel_count = self.config.kernels.len();
+        let channel_kernel = self.config.kernels
+            .get_mut(index)
+            .ok_or_else(|| anyhow!("there is no kernel {}, only {} kernels", index, kernel_count))?;
+        channel_kernel.kernel = kernel;
 
-        self.pad_wrap.recreate_bind_groups(device, grid, &fft_buffer);
-        self.fft.recreate_bind_groups(device, &fft_buffer);
-        self.transpose.recreate_bind_groups(device, &fft_buffer);
-        self.growth.recreate_bind_groups(device, grid, &fft_buffer);
+        self.regenerate_kernels(device, encoder, queue, grid);
 
-        self.kernel.recreate_bind_groups(
+        Ok(())
+    }
+
+    /// applies a new config in place, the grid is left alone. Kernels are only re-FFT'd if their shape or radius changed.
+    /// Changing the number of channels or kernels needs a new `FFTComputeState`.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        grid: &Storage,
+        config: SimulationConfig,
+    ) -> anyhow::Result<()> {
+        config.validate()?;
+
+        if config.channels != self.config.channels || config.kernels.len() != self.config.kernels.len() {
+            return Err(anyhow!(
+                "cannot update {} channels and {} kernels to {} channels and {} kernels in place",
+                self.config.channels, self.config.kernels.len(), config.channels, config.kernels.len()
+            ));
+        }
+
+        let kernels_changed = config.kernels
+            .iter()
+            .zip(&self.config.kernels)
+            .any(|(new, old)| new.kernel != old.kernel);
+
+        self.growth.uniforms.time_step = config.time_step;
+        self.growth.uniforms.update_rule = config.update_rule as u32;
+        self.growth.uniforms.growth_function = config.growth_function as u32;
+        self.pad_wrap.uniforms.boundary = config.boundary as u32;
+
+        let kernel_params = config.kernels.iter().map(KernelParams::from).collect::<Vec<_>>();
+        queue.write_buffer(self.buffers.kernel_params.buffer(), 0, bytemuck::cast_slice(&kernel_params));
+
+        self.config = config;
+
+        if kernels_changed {
+            self.regenerate_kernels(device, encoder, queue, grid);
+        }
+
+        Ok(())
+    }
+
+    /// re-FFTs every kernel, resizing the fft buffers if the largest radius doesn't fit in them anymore
+    fn regenerate_kernels(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        grid: &Storage,
+    ) {
+        let width = self.pad_wrap.uniforms.width;
+        let height = self.pad_wrap.uniforms.height;
+        let kernel_radius = self.config.max_radius();
+
+        if Self::fft_size(width, height, kernel_radius) != self.size() {
+            // also regenerates the kernels
+            self.handle_resize(device, encoder, queue, grid, height, width);
+            return;
+        }
+
+        self.pad_wrap.uniforms.radius = kernel_radius;
+
+        self.kernel.set_descriptions(
             device,
-            &fft_buffer,
+            &self.buffers,
             encoder,
             queue,
-            fft_size,
-            height,
-            width,
-            &mut self.fft,
-            &mut self.transpose,
+            self.config.kernels.iter().map(|k| k.kernel.clone()).collect(),
         );
-        
-        self.fft_buffer = fft_buffer;
     }
-}
\ No newline at end of file
+
+    pub fn set_boundary(&mut self, boundary: BoundaryMode) {
+        self.config.boundary = boundary;
+        self.pad_wrap.uniforms.boundary = boundary as u32;
+    }
+}
@@ -7,11 +7,46 @@ pub struct PadWrapState {
     pub uniforms: Uniforms<PadWrapUniforms>,
 }
 
+/// What the convolution sees past the edges of the grid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum BoundaryMode {
+    /// the grid wraps around, opposite edges are neighbors
+    #[default]
+    Torus = 0,
+    /// everything outside the grid is dead
+    Zero = 1,
+    /// the grid is mirrored across its edges
+    Reflect = 2,
+}
+
+impl BoundaryMode {
+    /// same mapping as `boundary_coord` in pad_wrap.wgsl, `None` if the cell is dead
+    pub fn map(self, c: i32, len: u32) -> Option<u32> {
+        let l = len as i32;
+
+        match self {
+            Self::Torus => Some(c.rem_euclid(l) as u32),
+            Self::Zero => (0..l).contains(&c).then_some(c as u32),
+            Self::Reflect => {
+                let m = c.rem_euclid(2 * l);
+                Some(if m >= l { 2 * l - 1 - m } else { m } as u32)
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, encase::ShaderType)]
 pub struct PadWrapUniforms {
     pub width: u32,
     pub height: u32,
-    pub size: u32,
+    pub fft_width: u32,
+    pub fft_height: u32,
+    pub channels: u32,
+    /// number of ghost cells around the grid, at least the largest kernel radius
+    pub radius: u32,
+    pub boundary: u32,
 }
 
 impl PadWrapState {
@@ -114,14 +149,12 @@ impl PadWrapState {
 
         pass.set_bind_group(0, &self.bind_group, &[]);
 
-        // let workgroups_x = (self.uniforms.width + 15) / 16;
-        // let workgroups_y = (self.uniforms.height + 15) / 16;
+        // let workgroups_x = self.uniforms.width.div_ceil(16);
+        // let workgroups_y = self.uniforms.height.div_ceil(16);
         // pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
 
-        let groups = (self.uniforms.size + 15) / 16;
-
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
-        pass.dispatch_workgroups(groups, groups, 1);
+        pass.dispatch_workgroups(self.uniforms.fft_width.div_ceil(16), self.uniforms.fft_height.div_ceil(16), self.uniforms.channels);
     }
 }
@@ -1,40 +1,93 @@
 struct PadWrapUniforms {
     width: u32,
     height: u32,
-    size: u32
+    fft_width: u32,
+    fft_height: u32,
+    channels: u32,
+    radius: u32,
+    boundary: u32,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: PadWrapUniforms;
 @group(0) @binding(1) var<storage, read> input: array<f32>;
-@group(0) @binding(2) var<storage, read_write> output: array<vec2<f32>>;
+// real cells, rows padded to hold the half spectra the fft leaves in their place, see fft.wgsl
+@group(0) @binding(2) var<storage, read_write> output: array<f32>;
 
-@compute @workgroup_size(16, 16) // each thread owns 1 grid entry
+const BOUNDARY_TORUS: u32 = 0u;
+const BOUNDARY_ZERO: u32 = 1u;
+const BOUNDARY_REFLECT: u32 = 2u;
+
+// signed grid coordinate of a padded cell, the ghost cells left of / above the world wrap around to the end of the plane
+fn unwrap_coord(p: u32, len: u32, size: u32) -> i32 {
+    if (p < len + uniforms.radius) {
+        return i32(p);
+    }
+    return i32(p) - i32(size);
+}
+
+// c modulo m in [0, m). Negative values are shifted up by a multiple of m first, `%` on negative numbers isn't reliable
+// on every backend (llvmpipe on GL treats them as unsigned)
+fn wrap(c: i32, m: i32) -> i32 {
+    let shifted = select(c, c + m * ((m - 1 - c) / m), c < 0);
+    return shifted % m;
+}
+
+// maps a coordinate that may be outside [0, len) back into the grid, -1 means the cell is dead
+fn boundary_coord(c: i32, len: u32) -> i32 {
+    let l = i32(len);
+
+    switch uniforms.boundary {
+        case BOUNDARY_ZERO: {
+            return select(-1, c, c >= 0 && c < l);
+        }
+        case BOUNDARY_REFLECT: {
+            // mirror across the edges, the edge cell is repeated
+            let m = wrap(c, 2 * l);
+            return select(m, 2 * l - 1 - m, m >= l);
+        }
+        default: {
+            return wrap(c, l);
+        }
+    }
+}
+
+@compute @workgroup_size(16, 16) // each thread owns 1 fft buffer entry
 fn pad_and_wrap(
     @builtin(global_invocation_id) g: vec3<u32>,
 ) {
-    let size = uniforms.size;
     let width = uniforms.width;
     let height = uniforms.height;
 
-    // centered offset
-    let off_x = (size - width) / 2u;
-    let off_y = (size - height) / 2u;
-
     let x = g.x;
     let y = g.y;
 
-    // for (var dy = 0u; dy < size; dy += height) {
-    //     for (var dx = 0u; dx < size; dx += width) {
-    //         let dst_x = (x + dx) % size;
-    //         let dst_y = (y + dy) % size;
-    //         output[dst_y * size + dst_x] = vec2<f32>(0.0, 0.0);
-    //     }
-    // }
+    if (x >= uniforms.fft_width || y >= uniforms.fft_height || g.z >= uniforms.channels) {
+        return;
+    }
+
+    // one plane per channel, in the grid and in the fft buffer
+    let input_offset = g.z * width * height;
+    let stride = 2u * (uniforms.fft_width / 2u + 1u);
+    let output_index = g.z * uniforms.fft_height * stride + y * stride + x;
+
+    // the world sits at the origin, with `radius` ghost cells on every side so the circular
+    // convolution sees the boundary instead of the other edge of the buffer
+    let ux = unwrap_coord(x, width, uniforms.fft_width);
+    let uy = unwrap_coord(y, height, uniforms.fft_height);
+    let r = i32(uniforms.radius);
 
-    if (x >= width || y >= height) { 
-        output[y * size + x] = vec2<f32>(0.0, 0.0);
+    if (ux < -r || ux >= i32(width) + r || uy < -r || uy >= i32(height) + r) {
+        output[output_index] = 0.0;
         return;
     }
 
-    output[y * size + x] = vec2<f32>(input[y * width + x], 0.0);
-}
\ No newline at end of file
+    let sx = boundary_coord(ux, width);
+    let sy = boundary_coord(uy, height);
+
+    if (sx < 0 || sy < 0) {
+        output[output_index] = 0.0;
+        return;
+    }
+
+    output[output_index] = input[input_offset + u32(sy) * width + u32(sx)];
+}
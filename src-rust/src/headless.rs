@@ -0,0 +1,304 @@
+//! Runs a simulation without a canvas, on the gpu through `FFTComputeState` or on the cpu through `CPUComputeState`.
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use crate::{colormap::Colormap, cpu_compute::CPUComputeState, fft_compute::{FFTComputeState, FFTState}, image::grid_to_rgba, random::cell_random, render::{CAPTURE_FORMAT, RenderState, RenderUniforms}, simulation::SimulationConfig, snapshot::Snapshot, storage_manager::Storage, uniforms_manager::Uniforms};
+
+/// steps recorded in one command encoder before it is submitted
+const STEPS_PER_SUBMIT: u64 = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// the best adapter wgpu can find
+    #[default]
+    Gpu,
+    /// wgpu's fallback adapter, a software rasterizer if one is installed
+    Software,
+    /// `CPUComputeState`, slow but needs no adapter at all
+    Cpu,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "gpu" => Ok(Self::Gpu),
+            "software" => Ok(Self::Software),
+            "cpu" => Ok(Self::Cpu),
+            _ => Err(anyhow!("unknown backend '{}', expected gpu, software or cpu", s)),
+        }
+    }
+}
+
+enum Compute {
+    Gpu {
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        grid: Storage,
+        fft_compute: Box<FFTComputeState>,
+        /// created with the first frame
+        render: Option<Box<RenderState>>,
+    },
+    Cpu {
+        cpu_compute: CPUComputeState,
+        grid: Vec<f32>,
+    },
+}
+
+pub struct HeadlessSimulation {
+    compute: Compute,
+    width: u32,
+    height: u32,
+    /// number of steps since the cells were loaded, continues the snapshot's counter
+    step: u64,
+}
+
+impl HeadlessSimulation {
+    /// `cells` holds `config.channels` planes of width x height cells
+    pub async fn new(
+        backend: Backend,
+        width: u32,
+        height: u32,
+        config: SimulationConfig,
+        cells: &[f32],
+        step: u64,
+    ) -> anyhow::Result<Self> {
+        config.validate()?;
+
+        if cells.len() != (width * height * config.channels) as usize {
+            return Err(anyhow!(
+                "expected {} cells for {} channels of {}x{}, got {}",
+                width * height * config.channels, config.channels, width, height, cells.len()
+            ));
+        }
+
+        let compute = match backend {
+            Backend::Cpu => Compute::Cpu {
+                cpu_compute: CPUComputeState::new(width, height, config)?,
+                grid: cells.to_vec(),
+            },
+            Backend::Gpu | Backend::Software => {
+                let (device, queue) = request_device(backend == Backend::Software).await?;
+
+                let grid = Storage::new(&device, "Grid", cells);
+
+                let mut encoder = device.create_command_encoder(&Default::default());
+                let fft_compute = Box::new(FFTComputeState::new(&device, &mut encoder, &queue, &grid, width, height, config));
+                queue.submit(Some(encoder.finish()));
+
+                Compute::Gpu { device, queue, grid, fft_compute, render: None }
+            }
+        };
+
+        Ok(Self {
+            compute,
+            width,
+            height,
+            step,
+        })
+    }
+
+    pub async fn from_snapshot(backend: Backend, snapshot: Snapshot) -> anyhow::Result<Self> {
+        Self::new(backend, snapshot.width, snapshot.height, snapshot.config, &snapshot.cells, snapshot.step).await
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn step_count(&self) -> u64 {
+        self.step
+    }
+
+    /// simulated time, the config can't change so it is steps / time_step
+    pub fn time(&self) -> f64 {
+        self.step as f64 / self.config().time_step as f64
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        match &self.compute {
+            Compute::Gpu { fft_compute, .. } => fft_compute.config(),
+            Compute::Cpu { cpu_compute, .. } => cpu_compute.config(),
+        }
+    }
+
+    pub fn run(&mut self, steps: u64) {
+        match &mut self.compute {
+            Compute::Gpu { device, queue, fft_compute, .. } => {
+                let mut remaining = steps;
+                while remaining > 0 {
+                    let batch = remaining.min(STEPS_PER_SUBMIT);
+
+                    let mut encoder = device.create_command_encoder(&Default::default());
+                    for _ in 0..batch {
+                        fft_compute.run(&mut encoder, queue);
+                    }
+                    queue.submit(Some(encoder.finish()));
+
+                    remaining -= batch;
+                }
+            }
+            Compute::Cpu { cpu_compute, grid } => {
+                for _ in 0..steps {
+                    cpu_compute.step(grid);
+                }
+            }
+        }
+
+        self.step += steps;
+    }
+
+    /// waits for the gpu to finish the recorded steps and reads the grid back
+    pub async fn cells(&self) -> anyhow::Result<Vec<f32>> {
+        match &self.compute {
+            Compute::Gpu { device, queue, grid, .. } => {
+                let bytes = grid.read(device, queue).await?;
+                Ok(bytemuck::pod_collect_to_vec(&bytes))
+            }
+            Compute::Cpu { grid, .. } => Ok(grid.clone()),
+        }
+    }
+
+    /// the world at one pixel per cell as rgba8 rows, drawn by `RenderState` on the gpu backends
+    pub async fn frame(&mut self, colormap: &Colormap) -> anyhow::Result<Vec<u8>> {
+        let width = self.width;
+        let height = self.height;
+
+        match &mut self.compute {
+            Compute::Gpu { device, queue, grid, fft_compute, render } => {
+                let render = render.get_or_insert_with(|| {
+                    let uniforms = Uniforms::new(device, "Render", RenderUniforms {
+                        width,
+                        height,
+                        channels: fft_compute.config().channels,
+                        view_width: width,
+                        view_height: height,
+                        zoom: 1.0,
+                        center_x: width as f32 / 2.0,
+                        center_y: height as f32 / 2.0,
+                        range_max: 1.0,
+                        fft_width: fft_compute.size().0,
+                        fft_height: fft_compute.size().1,
+                        ..Default::default()
+                    });
+                    Box::new(RenderState::new(device, grid, fft_compute.buffers(), uniforms, CAPTURE_FORMAT))
+                });
+
+                render.set_colormap(queue, colormap);
+                render.capture(device, queue, width, height).await
+            }
+            Compute::Cpu { cpu_compute, grid } => Ok(grid_to_rgba(grid, width, height, cpu_compute.config().channels, colormap)),
+        }
+    }
+
+    pub async fn snapshot(&self) -> anyhow::Result<Snapshot> {
+        Ok(Snapshot {
+            width: self.width,
+            height: self.height,
+            step: self.step,
+            config: self.config().clone(),
+            cells: self.cells().await?,
+        })
+    }
+}
+
+/// device for the gpu backends, `software` asks for wgpu's fallback adapter
+pub async fn request_device(software: bool) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&Default::default());
+
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptionsBase {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: software,
+            compatible_surface: None,
+        })
+        .await?;
+
+    log::info!("running on {:?}", adapter.get_info());
+
+    Ok(adapter.request_device(&wgpu::DeviceDescriptor {
+        required_limits: FFTState::required_limits(&adapter),
+        ..Default::default()
+    }).await?)
+}
+
+/// a square of noise in the middle of an empty grid, `size` cells wide.
+/// The cells are keyed by their position in the square, so a seed gives the same square on every world size and backend.
+pub fn noise(width: u32, height: u32, channels: u32, size: u32, density: f32, seed: u32) -> Vec<f32> {
+    let mut grid = vec![0f32; (width * height * channels) as usize];
+
+    let size_x = size.min(width);
+    let size_y = size.min(height);
+    let left = (width - size_x) / 2;
+    let top = (height - size_y) / 2;
+
+    for (channel, plane) in grid.chunks_exact_mut((width * height) as usize).enumerate() {
+        // even streams hold the values, odd ones decide which cells are alive
+        let stream = channel as u32 * 2;
+
+        for y in 0..size_y {
+            for x in 0..size_x {
+                if cell_random(seed, x, y, stream + 1) < density {
+                    plane[((top + y) * width + left + x) as usize] = cell_random(seed, x, y, stream);
+                }
+            }
+        }
+    }
+
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fft_compute::{BoundaryMode, UpdateRule}, kernel_description::KernelDescription, simulation::ChannelKernel};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn gpu_matches_cpu() {
+        let cases = [
+            (BoundaryMode::Torus, UpdateRule::Euler),
+            (BoundaryMode::Reflect, UpdateRule::Euler),
+            (BoundaryMode::Torus, UpdateRule::SoftClip),
+        ];
+
+        for (boundary, update_rule) in cases {
+            for (width, height, radius) in [(108, 100, 14), (30, 20, 25)] {
+                let config = SimulationConfig {
+                    boundary,
+                    update_rule,
+                    kernels: vec![ChannelKernel { kernel: KernelDescription { radius, ..Default::default() }, ..Default::default() }],
+                    ..Default::default()
+                };
+                let cells = noise(width, height, 1, width.max(height), 0.5, 5);
+
+                let mut gpu = match HeadlessSimulation::new(Backend::Gpu, width, height, config.clone(), &cells, 0).await {
+                    Ok(gpu) => gpu,
+                    Err(err) => {
+                        eprintln!("skipping, no adapter: {}", err);
+                        return;
+                    }
+                };
+                let mut cpu = HeadlessSimulation::new(Backend::Cpu, width, height, config, &cells, 0).await.unwrap();
+
+                gpu.run(4);
+                cpu.run(4);
+
+                let expected = cpu.cells().await.unwrap();
+                let max_diff = gpu.cells().await.unwrap()
+                    .iter()
+                    .zip(&expected)
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0f32, f32::max);
+                assert!(max_diff < 1e-5, "{:?} {:?} {}x{} radius {}: max diff {}", boundary, update_rule, width, height, radius, max_diff);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Minimal PNG and PPM encoders for frames, all images are rgba8 row by row.
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use crate::colormap::Colormap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn encode(self, width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Png => encode_png(width, height, rgba),
+            Self::Ppm => encode_ppm(width, height, rgba),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Ppm => "ppm",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "png" => Ok(Self::Png),
+            "ppm" => Ok(Self::Ppm),
+            _ => Err(anyhow!("unknown image format '{}', expected png or ppm", s)),
+        }
+    }
+}
+
+/// colors a channels x height x width grid like render.wgsl does: one channel goes through the colormap,
+/// more are shown as rgb, the first three at most
+pub fn grid_to_rgba(grid: &[f32], width: u32, height: u32, channels: u32, colormap: &Colormap) -> Vec<u8> {
+    let plane_size = (width * height) as usize;
+    let to_byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    (0..plane_size)
+        .flat_map(|i| {
+            let [r, g, b] = if channels > 1 {
+                let mut rgb = [0.0; 3];
+                for (c, value) in rgb.iter_mut().enumerate().take(channels.min(3) as usize) {
+                    *value = grid[c * plane_size + i];
+                }
+                rgb
+            } else {
+                colormap.eval(grid[i])
+            };
+
+            [to_byte(r), to_byte(g), to_byte(b), 255]
+        })
+        .collect()
+}
+
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    // every row starts with its filter type, 0 leaves it as is
+    let row_size = width as usize * 4;
+    let mut raw = Vec::with_capacity((row_size + 1) * height as usize);
+    for row in rgba.chunks_exact(row_size) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per sample, rgba, deflate, no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+/// binary PPM, the alpha channel is dropped
+pub fn encode_ppm(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for pixel in rgba.chunks_exact(4) {
+        ppm.extend_from_slice(&pixel[..3]);
+    }
+
+    ppm
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    // the crc covers the chunk type and the data
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,147 @@
+use anyhow::anyhow;
+
+/// Shape of the bump that fills each kernel shell
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KernelCore {
+    /// bell curve centered in the shell, the original kernel
+    #[default]
+    Gaussian,
+    /// exp(4 - 1 / (r(1 - r)))
+    Exponential,
+    /// (4r(1 - r))^4
+    Polynomial,
+    /// 1 on the middle half of the shell, 0 elsewhere
+    Rectangular,
+}
+
+impl KernelCore {
+    /// evaluates the core at r in [0, 1], the position inside a shell
+    pub fn eval(self, r: f32) -> f32 {
+        if r <= 0.0 || r >= 1.0 {
+            return 0.0;
+        }
+
+        match self {
+            Self::Gaussian => bell(r, 0.5, 0.15),
+            Self::Exponential => (4.0 - 1.0 / (r * (1.0 - r))).exp(),
+            Self::Polynomial => (4.0 * r * (1.0 - r)).powi(4),
+            Self::Rectangular => if (0.25..=0.75).contains(&r) { 1.0 } else { 0.0 },
+        }
+    }
+}
+
+/// Radially symmetric Lenia kernel, made of concentric shells with peak heights `shells` (the `b` vector)
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct KernelDescription {
+    pub radius: u32,
+    pub shells: Vec<f32>,
+    pub core: KernelCore,
+    /// fraction of each shell covered by the core, 1.0 spans the whole shell
+    pub width: f32,
+}
+
+impl Default for KernelDescription {
+    fn default() -> Self {
+        Self {
+            radius: 40,
+            shells: vec![1.0],
+            core: KernelCore::Gaussian,
+            width: 1.0,
+        }
+    }
+}
+
+impl KernelDescription {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.radius == 0 {
+            return Err(anyhow!("kernel radius has to be at least 1"));
+        }
+
+        if self.shells.is_empty() {
+            return Err(anyhow!("kernel needs at least one shell"));
+        }
+
+        if self.shells.iter().any(|b| !b.is_finite()) {
+            return Err(anyhow!("kernel shell heights have to be finite"));
+        }
+
+        if !self.width.is_finite() || self.width <= 0.0 {
+            return Err(anyhow!("kernel core width has to be a positive number"));
+        }
+
+        // a radius of 1 only covers the rim of the core, which is 0, and zero shells sum to 0 as well.
+        // Normalizing such a kernel divides by 0 and fills the grid with NaN.
+        let sum = self.sum();
+        if !sum.is_finite() || sum <= 0.0 {
+            return Err(anyhow!("kernel sums to {}, it needs some positive weight inside the radius", sum));
+        }
+
+        Ok(())
+    }
+
+    /// kernel value at normalized distance d from the center, where d = 1 is the kernel radius
+    pub fn eval(&self, d: f32) -> f32 {
+        if d >= 1.0 || self.shells.is_empty() {
+            return 0.0;
+        }
+
+        let shell_count = self.shells.len();
+        let br = d * shell_count as f32;
+        let shell = (br as usize).min(shell_count - 1);
+
+        // stretch the core around the middle of the shell
+        let r = 0.5 + (br - shell as f32 - 0.5) / self.width;
+
+        self.shells[shell] * self.core.eval(r)
+    }
+
+    /// unnormalized (2r+1)x(2r+1) kernel, centered at [radius][radius]
+    pub fn generate(&self) -> Vec<Vec<f32>> {
+        let radius = self.radius as i32;
+        let size = (2 * radius + 1) as usize;
+
+        let mut kernel = vec![vec![0f32; size]; size];
+
+        for (i, row) in kernel.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                let y = (i as i32 - radius) as f32;
+                let x = (j as i32 - radius) as f32;
+
+                let d = (x.powi(2) + y.powi(2)).sqrt() / self.radius as f32;
+
+                *value = self.eval(d);
+            }
+        }
+
+        kernel
+    }
+
+    /// sum of the unnormalized kernel from `generate`
+    pub fn sum(&self) -> f32 {
+        self.generate().iter().map(|r| r.iter().sum::<f32>()).sum()
+    }
+
+    /// kernel normalized to sum 1 and wrapped around the origin of a width x height plane, ready to be FFT'd
+    pub fn wrapped(&self, width: u32, height: u32) -> Vec<f32> {
+        let kernel_radius = self.radius as i32;
+        let kernel_data = self.generate();
+        let kernel_sum: f32 = kernel_data.iter().map(|r| r.iter().sum::<f32>()).sum();
+
+        let mut wrapped = vec![0f32; (width * height) as usize];
+
+        for (i, row) in kernel_data.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let pi = (i as i32 - kernel_radius).rem_euclid(height as i32) as u32;
+                let pj = (j as i32 - kernel_radius).rem_euclid(width as i32) as u32;
+                wrapped[(pi * width + pj) as usize] = value / kernel_sum;
+            }
+        }
+
+        wrapped
+    }
+}
+
+fn bell(x: f32, m: f32, s: f32) -> f32 {
+    (-(((x - m) / s).powi(2)) / 2.0).exp()
+}
@@ -1,12 +1,26 @@
-mod compute;
-mod random;
-#[cfg(target_arch = "wasm32")]
-mod render;
+pub mod animal;
+pub mod brush;
+pub mod colormap;
+pub mod compute;
+pub mod cpu_compute;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+pub mod image;
+pub mod random;
+pub mod recorder;
+pub mod render;
+pub mod resample;
 #[cfg(target_arch = "wasm32")]
 mod state;
-mod fft_compute;
-mod uniforms_manager;
-mod storage_manager;
+pub mod fft_compute;
+pub mod kernel_description;
+pub mod simulation;
+pub mod snapshot;
+pub mod stats;
+pub mod tracking;
+pub mod uniforms_manager;
+pub mod storage_manager;
+pub mod reduction_manager;
 
 #[cfg(target_arch = "wasm32")]
 pub use wasm_interface::*;
@@ -14,7 +28,7 @@ pub use wasm_interface::*;
 #[cfg(target_arch = "wasm32")]
 mod wasm_interface {
 
-    use crate::state::State;
+    use crate::{animal::Animal, brush::{Brush, Pattern}, colormap::Colormap, image::ImageFormat, fft_compute::{BoundaryMode, GrowthFunction, UpdateRule}, kernel_description::KernelDescription, render::{FilterMode, RenderMode}, resample::ResampleMode, simulation::SimulationConfig, state::State};
     use wasm_bindgen::prelude::*;
 
     #[derive(serde::Deserialize, serde::Serialize)]
@@ -22,9 +36,15 @@ mod wasm_interface {
         pub random_seed: u32,
         pub random_density: f32,
         pub random_brush_size: u32,
-        pub compute_time_step: u32,
+        pub compute_time_step: f32,
         pub compute_m: f32,
         pub compute_s: f32,
+        pub compute_radius: u32,
+        pub compute_growth_function: GrowthFunction,
+        pub compute_boundary: BoundaryMode,
+        pub compute_update_rule: UpdateRule,
+        pub world_resample: ResampleMode,
+        pub render_filter: FilterMode,
     }
 
 
@@ -37,6 +57,129 @@ mod wasm_interface {
             compute_time_step: number,
             compute_m: number,
             compute_s: number,
+            compute_radius: number,
+            compute_growth_function: GrowthFunction,
+            compute_boundary: BoundaryMode,
+            compute_update_rule: UpdateRule,
+            world_resample: ResampleMode,
+            render_filter: FilterMode,
+        }
+
+        type GrowthFunction = "gaussian" | "polynomial" | "rectangular" | "life";
+
+        type BoundaryMode = "torus" | "zero" | "reflect";
+
+        type UpdateRule = "euler" | "soft_clip" | "asymptotic" | "mass_conserving";
+
+        type ResampleMode = "crop" | "nearest" | "bilinear" | "tile";
+
+        type FilterMode = "nearest" | "bilinear" | "bicubic";
+
+        type RenderMode = "state" | "potential" | "growth" | "delta";
+
+        type ImageFormat = "png" | "ppm";
+    "#;
+
+    #[wasm_bindgen(typescript_custom_section)]
+    const KERNEL_DESCRIPTION_TS: &'static str = r#"
+        type KernelCore = "gaussian" | "exponential" | "polynomial" | "rectangular";
+
+        type KernelDescription = {
+            radius: number,
+            shells: number[],
+            core: KernelCore,
+            width: number,
+        }
+
+        type ChannelKernel = {
+            kernel: KernelDescription,
+            source: number,
+            target: number,
+            m: number,
+            s: number,
+            weight: number,
+        }
+
+        type SimulationConfig = {
+            channels: number,
+            time_step: number,
+            growth_function: GrowthFunction,
+            boundary?: BoundaryMode,
+            update_rule?: UpdateRule,
+            kernels: ChannelKernel[],
+        }
+    "#;
+
+    #[wasm_bindgen(typescript_custom_section)]
+    const COLORMAP_TS: &'static str = r#"
+        type ColorStop = {
+            position: number,
+            color: [number, number, number],
+        }
+
+        type Palette = "viridis" | "magma" | "inferno" | "turbo" | "grayscale" | "jet" | { gradient: ColorStop[] };
+
+        type Colormap = {
+            palette: Palette,
+            reversed?: boolean,
+            gamma?: number,
+        }
+    "#;
+
+    #[wasm_bindgen(typescript_custom_section)]
+    const BRUSH_TS: &'static str = r#"
+        type BrushShape = "circle" | "square" | "gaussian";
+
+        type BrushFill = "random" | "constant" | "eraser";
+
+        type BrushBlend = "replace" | "add";
+
+        type Brush = {
+            shape: BrushShape,
+            fill: BrushFill,
+            blend: BrushBlend,
+            size: number,
+            value: number,
+            softness?: number,
+            rotation?: number,
+            mirror?: boolean,
+        }
+    "#;
+
+    #[wasm_bindgen(typescript_custom_section)]
+    const STATS_TS: &'static str = r#"
+        type Bounds = {
+            min_x: number,
+            min_y: number,
+            max_x: number,
+            max_y: number,
+        }
+
+        type Stats = {
+            step: number,
+            time: number,
+            mass: number,
+            mean: number,
+            variance: number,
+            live: number,
+            center?: [number, number],
+            bounds?: Bounds,
+        }
+    "#;
+
+    #[wasm_bindgen(typescript_custom_section)]
+    const TRACKING_TS: &'static str = r#"
+        type TrackedObject = {
+            id: number,
+            since: number,
+            object: {
+                mass: number,
+                cells: number,
+                center: [number, number],
+                orientation: number,
+                extent: [number, number],
+            },
+            velocity: [number, number],
         }
     "#;
 
@@ -45,11 +188,37 @@ mod wasm_interface {
         #[wasm_bindgen(typescript_type = "ParametersTs")]
         #[derive(Debug)]
         pub type ParametersTs;
+
+        #[wasm_bindgen(typescript_type = "KernelDescription")]
+        #[derive(Debug)]
+        pub type KernelDescriptionTs;
+
+        #[wasm_bindgen(typescript_type = "SimulationConfig")]
+        #[derive(Debug)]
+        pub type SimulationConfigTs;
+
+        #[wasm_bindgen(typescript_type = "RenderMode")]
+        #[derive(Debug)]
+        pub type RenderModeTs;
+
+        #[wasm_bindgen(typescript_type = "ImageFormat")]
+        #[derive(Debug)]
+        pub type ImageFormatTs;
+
+        #[wasm_bindgen(typescript_type = "Colormap")]
+        #[derive(Debug)]
+        pub type ColormapTs;
+
+        #[wasm_bindgen(typescript_type = "Brush")]
+        #[derive(Debug)]
+        pub type BrushTs;
     }
 
     #[wasm_bindgen]
     pub struct App {
         state: State,
+        /// called with the name and bytes of every recorded frame
+        on_frame: Option<js_sys::Function>,
     }
 
     #[wasm_bindgen]
@@ -63,12 +232,53 @@ mod wasm_interface {
 
             let state = State::new(canvas).await.unwrap();
 
-            Self { state }
+            Self { state, on_frame: None }
         }
 
         #[wasm_bindgen]
         pub fn step(&mut self) {
             self.state.step();
+
+            if let (Some((name, frame)), Some(on_frame)) = (self.state.record_frame(), self.on_frame.clone()) {
+                wasm_bindgen_futures::spawn_local(async move {
+                    match frame.await {
+                        Ok(bytes) => {
+                            let bytes = js_sys::Uint8Array::from(bytes.as_slice());
+                            if let Err(e) = on_frame.call2(&JsValue::NULL, &JsValue::from_str(&name), &bytes) {
+                                log::warn!("frame callback failed: {:?}", e);
+                            }
+                        }
+                        Err(e) => log::warn!("could not record {}: {}", name, e),
+                    }
+                });
+            }
+        }
+
+        /// resolves with the canvas as an image, drawn at width x height if they are given
+        #[wasm_bindgen]
+        pub fn capture_frame(&mut self, format: ImageFormatTs, width: Option<u32>, height: Option<u32>) -> Result<js_sys::Promise, JsError> {
+            let format: ImageFormat = serde_wasm_bindgen::from_value(format.dyn_into::<JsValue>().unwrap())?;
+            let frame = self.state.capture_frame(format, width.zip(height));
+
+            Ok(wasm_bindgen_futures::future_to_promise(async move {
+                let bytes = frame.await.map_err(|e| JsError::new(&e.to_string()))?;
+                Ok(js_sys::Uint8Array::from(bytes.as_slice()).into())
+            }))
+        }
+
+        /// captures every `every`th step and passes it to `on_frame(name, bytes)`, names are numbered from frame_000000
+        #[wasm_bindgen]
+        pub fn start_recording(&mut self, every: u32, format: ImageFormatTs, on_frame: js_sys::Function) -> Result<(), JsError> {
+            let format: ImageFormat = serde_wasm_bindgen::from_value(format.dyn_into::<JsValue>().unwrap())?;
+            self.state.start_recording(every as u64, format);
+            self.on_frame = Some(on_frame);
+            Ok(())
+        }
+
+        #[wasm_bindgen]
+        pub fn stop_recording(&mut self) {
+            self.state.stop_recording();
+            self.on_frame = None;
         }
 
         #[wasm_bindgen]
@@ -81,9 +291,116 @@ mod wasm_interface {
             self.state.resize(width, height);
         }
 
+        /// fixes the simulation at width x height cells, the canvas then only changes the view
+        #[wasm_bindgen]
+        pub fn set_world_size(&mut self, width: u32, height: u32) {
+            self.state.set_world_size(Some((width, height)));
+        }
+
+        /// makes the simulation follow the canvas size again
+        #[wasm_bindgen]
+        pub fn fit_world_to_canvas(&mut self) {
+            self.state.set_world_size(None);
+        }
+
+        /// zooms by `factor` around the canvas pixel (x, y)
+        #[wasm_bindgen]
+        pub fn zoom(&mut self, factor: f32, x: f32, y: f32) {
+            self.state.zoom_at(factor, x, y);
+        }
+
+        /// moves the view by (dx, dy) canvas pixels
         #[wasm_bindgen]
-        pub fn randomize(&mut self, x: u32, y: u32) {
-            self.state.randomize_area(x, y);
+        pub fn pan(&mut self, dx: f32, dy: f32) {
+            self.state.pan(dx, dy);
+        }
+
+        #[wasm_bindgen]
+        pub fn reset_view(&mut self) {
+            self.state.reset_camera();
+        }
+
+        /// `[x, y]` of the cell under the canvas pixel (x, y), undefined outside the world
+        #[wasm_bindgen]
+        pub fn screen_to_cell(&self, x: f32, y: f32) -> Option<Vec<u32>> {
+            self.state.screen_to_cell(x, y).map(|(x, y)| vec![x, y])
+        }
+
+        /// picks the field drawn on the canvas. `layer` is the kernel shown by "potential" and the channel shown
+        /// by "growth" and "delta". The values between `min` and `max` span the colormap, a fitting range is used if they are left out
+        #[wasm_bindgen]
+        pub fn set_render_mode(&mut self, mode: RenderModeTs, layer: u32, min: Option<f32>, max: Option<f32>) -> Result<(), JsError> {
+            let mode: RenderMode = serde_wasm_bindgen::from_value(mode.dyn_into::<JsValue>().unwrap())?;
+            let range = min.zip(max);
+            if range.is_some_and(|(min, max)| min >= max) {
+                return Err(JsError::new("the range minimum has to be below its maximum"));
+            }
+
+            self.state.set_render_mode(mode, layer, range);
+            Ok(())
+        }
+
+        /// resolves with the smallest and largest value of a render mode as [min, max], for fitting its range
+        #[wasm_bindgen]
+        pub fn value_range(&mut self, mode: RenderModeTs, layer: u32) -> Result<js_sys::Promise, JsError> {
+            let mode: RenderMode = serde_wasm_bindgen::from_value(mode.dyn_into::<JsValue>().unwrap())?;
+            let range = self.state.value_range(mode, layer);
+
+            Ok(wasm_bindgen_futures::future_to_promise(async move {
+                let (min, max) = range.await.map_err(|e| JsError::new(&e.to_string()))?;
+                Ok(js_sys::Array::of2(&min.into(), &max.into()).into())
+            }))
+        }
+
+        /// paints values outside the render range in magenta instead of clamping them
+        #[wasm_bindgen]
+        pub fn set_out_of_range_highlight(&mut self, highlight: bool) {
+            self.state.set_out_of_range_highlight(highlight);
+        }
+
+        /// switches the palette used to draw the world, takes effect on the next frame
+        #[wasm_bindgen]
+        pub fn set_colormap(&mut self, colormap: ColormapTs) -> Result<(), JsError> {
+            let colormap: Colormap = serde_wasm_bindgen::from_value(colormap.dyn_into::<JsValue>().unwrap())?;
+            self.state.set_colormap(colormap).map_err(|e| JsError::new(&e.to_string()))
+        }
+
+        /// size and value are overwritten by the random_brush_size and random_density parameters
+        #[wasm_bindgen]
+        pub fn set_brush(&mut self, brush: BrushTs) -> Result<(), JsError> {
+            let brush: Brush = serde_wasm_bindgen::from_value(brush.dyn_into::<JsValue>().unwrap())?;
+            self.state.set_brush(brush).map_err(|e| JsError::new(&e.to_string()))
+        }
+
+        /// paints the brush centered on the canvas pixel (x, y)
+        #[wasm_bindgen]
+        pub fn paint(&mut self, x: u32, y: u32) {
+            self.state.paint_area(x, y);
+        }
+
+        /// `cells` holds `channels` planes of width x height cells
+        #[wasm_bindgen]
+        pub fn set_pattern(&mut self, width: u32, height: u32, channels: u32, cells: Vec<f32>) -> Result<(), JsError> {
+            self.state.set_pattern(Pattern { width, height, channels, cells }).map_err(|e| JsError::new(&e.to_string()))
+        }
+
+        /// uses the cells of a catalogue entry as the pattern, its config is ignored
+        #[wasm_bindgen]
+        pub fn set_pattern_from_animal(&mut self, json: &str) -> Result<(), JsError> {
+            let animal = Animal::from_json(json).map_err(|e| JsError::new(&e.to_string()))?;
+            let pattern = Pattern {
+                width: animal.width,
+                height: animal.height,
+                channels: animal.config.channels,
+                cells: animal.cells,
+            };
+            self.state.set_pattern(pattern).map_err(|e| JsError::new(&e.to_string()))
+        }
+
+        /// pastes the pattern centered on the canvas pixel (x, y) with the brush's rotation, mirroring and blend
+        #[wasm_bindgen]
+        pub fn paste_pattern(&mut self, x: u32, y: u32) {
+            self.state.paste_pattern(x, y);
         }
 
         #[wasm_bindgen]
@@ -92,9 +409,94 @@ mod wasm_interface {
         }
 
         #[wasm_bindgen]
-        pub fn set_parameters(&mut self, parameters: ParametersTs) {
-            let parameters = serde_wasm_bindgen::from_value(parameters.dyn_into::<JsValue>().unwrap()).unwrap();
-            self.state.parse_parameters(parameters);
+        pub fn set_parameters(&mut self, parameters: ParametersTs) -> Result<(), JsError> {
+            let parameters = serde_wasm_bindgen::from_value(parameters.dyn_into::<JsValue>().unwrap())?;
+            self.state.parse_parameters(parameters).map_err(|e| JsError::new(&e.to_string()))
+        }
+
+        #[wasm_bindgen]
+        pub fn config(&self) -> Result<SimulationConfigTs, JsError> {
+            Ok(serde_wasm_bindgen::to_value(self.state.simulation_config())?.unchecked_into())
+        }
+
+        #[wasm_bindgen]
+        pub fn set_kernel(&mut self, index: usize, kernel: KernelDescriptionTs) -> Result<(), JsError> {
+            let kernel: KernelDescription = serde_wasm_bindgen::from_value(kernel.dyn_into::<JsValue>().unwrap())?;
+            self.state.set_kernel(index, kernel).map_err(|e| JsError::new(&e.to_string()))
+        }
+
+        /// resolves with the world as a snapshot file, see `snapshot.rs`
+        #[wasm_bindgen]
+        pub fn export_state(&mut self, compress: bool) -> js_sys::Promise {
+            let snapshot = self.state.export_state(compress);
+
+            wasm_bindgen_futures::future_to_promise(async move {
+                let bytes = snapshot.await.map_err(|e| JsError::new(&e.to_string()))?;
+                Ok(js_sys::Uint8Array::from(bytes.as_slice()).into())
+            })
+        }
+
+        #[wasm_bindgen]
+        pub fn import_state(&mut self, bytes: &[u8]) -> Result<(), JsError> {
+            self.state.import_state(bytes).map_err(|e| JsError::new(&e.to_string()))
+        }
+
+        /// stamps a creature from the Lenia animal catalogue centered on (x, y) and switches to its parameters
+        #[wasm_bindgen]
+        pub fn load_animal(&mut self, json: &str, x: u32, y: u32) -> Result<(), JsError> {
+            let animal = Animal::from_json(json).map_err(|e| JsError::new(&e.to_string()))?;
+            self.state.load_animal(animal, x, y).map_err(|e| JsError::new(&e.to_string()))
+        }
+
+        /// steps since the world was last cleared
+        #[wasm_bindgen]
+        pub fn step_count(&self) -> f64 {
+            self.state.step_count() as f64
+        }
+
+        /// simulated time, the sum of 1 / time_step over every step
+        #[wasm_bindgen]
+        pub fn time(&self) -> f64 {
+            self.state.time()
+        }
+
+        /// resolves with the `Stats` of the grid, cells above `threshold` count as alive
+        #[wasm_bindgen]
+        pub fn stats(&mut self, threshold: f32) -> js_sys::Promise {
+            let stats = self.state.stats(threshold);
+
+            wasm_bindgen_futures::future_to_promise(async move {
+                let stats = stats.await.map_err(|e| JsError::new(&e.to_string()))?;
+                Ok(serde_wasm_bindgen::to_value(&stats)?)
+            })
+        }
+
+        /// resolves with the `TrackedObject`s above `threshold`, ids carry over from the last call
+        #[wasm_bindgen]
+        pub fn track(&mut self, threshold: f32) -> js_sys::Promise {
+            let objects = self.state.track(threshold);
+
+            wasm_bindgen_futures::future_to_promise(async move {
+                let objects = objects.await.map_err(|e| JsError::new(&e.to_string()))?;
+                Ok(serde_wasm_bindgen::to_value(&objects)?)
+            })
+        }
+
+        /// resolves with everything alive as a catalogue entry
+        #[wasm_bindgen]
+        pub fn export_animal(&mut self, code: String, name: String) -> js_sys::Promise {
+            let animal = self.state.export_animal(code, name);
+
+            wasm_bindgen_futures::future_to_promise(async move {
+                let json = animal.await.map_err(|e| JsError::new(&e.to_string()))?;
+                Ok(JsValue::from_str(&json))
+            })
+        }
+
+        #[wasm_bindgen]
+        pub fn configure(&mut self, config: SimulationConfigTs) -> Result<(), JsError> {
+            let config: SimulationConfig = serde_wasm_bindgen::from_value(config.dyn_into::<JsValue>().unwrap())?;
+            self.state.configure(config).map_err(|e| JsError::new(&e.to_string()))
         }
     }
 
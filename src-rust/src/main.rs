@@ -1,166 +1,457 @@
-#[cfg(not(target_arch = "wasm32"))]
-mod fft_compute;
-#[cfg(not(target_arch = "wasm32"))]
-mod uniforms_manager;
-#[cfg(not(target_arch = "wasm32"))]
-mod storage_manager;
-
 #[cfg(target_arch = "wasm32")]
 fn main() {
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "\
+usage: lenia-web <command> [options]
+
+commands:
+    run       advances a world and writes snapshots or frames along the way
+    sweep     runs the same world over a grid of growth parameters and prints a csv summary
+    track     follows the creatures of a world and prints their trajectories as csv
+    compare   steps the gpu and the cpu simulation side by side and prints their difference
+
+world, for run, sweep and track:
+    --snapshot <file>     resumes a snapshot written by the app or by `run`
+    --animal <file>       stamps a catalogue animal in the middle of the world, with its parameters
+    --seed <n>            fills a square in the middle with noise, the default (seed 1)
+    --noise-size <n>      side of the noise square, a quarter of the world by default
+    --density <f>         fraction of the noise square that is alive, 0.5 by default
+    --config <file>       SimulationConfig as JSON, overrides the parameters of the animal or snapshot
+    --size <w>x<h>        world size, 256x256 by default, ignored for snapshots
+    --backend <b>         gpu, software or cpu, gpu by default
+
+run:
+    --steps <n>           number of steps, 100 by default
+    --every <n>           writes an output every n steps, only the last step by default
+    --out <dir>           output directory, `out` by default
+    --format <f>          snapshot, png or ppm, snapshot by default. Images are numbered frame_000000.png and up
+    --colormap <name>     palette of the images, viridis by default
+
+sweep:
+    --steps <n>           steps per run, 100 by default
+    --m <a:b:step>        values of the growth center m, applied to every kernel
+    --s <a:b:step>        values of the growth width s
+    --radius <a:b:step>   kernel radii
+    --time-step <a:b:step>
+    --out <dir>           also writes the last frame of every run as a png
+    --colormap <name>
+
+track:
+    --steps <n>           100 by default
+    --every <n>           steps between readings, 1 by default. Creatures moving more than their radius in between lose their id
+    --threshold <f>       cells above it are alive, 0.1 by default
+
+compare:
+    --steps <n>           10 by default
+    --size <w>x<h>        96x64 by default
+    --seed <n>, --noise-size <n>, --density <f>
+                          noise square of about four kernel radii by default, see world
+";
+
 #[cfg(not(target_arch = "wasm32"))]
 #[tokio::main]
 async fn main() {
-        use crate::{fft_compute::{FFTComputeState, FFTState, FFTUniforms, GrowthState, GrowthUniforms, PadWrapState, PadWrapUniforms, TransposeState, TransposeUniforms}, storage_manager::Storage};
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let Some((command, options)) = args.split_first() else {
+        eprint!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let result = match cli::Options::parse(options) {
+        Ok(options) => match command.as_str() {
+            "run" => cli::run(options).await,
+            "sweep" => cli::sweep(options).await,
+            "track" => cli::track(options).await,
+            "compare" => cli::compare(options).await,
+            "help" | "--help" | "-h" => {
+                print!("{}", USAGE);
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!("unknown command '{}'", command)),
+        },
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+}
 
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {..Default::default()});
-    
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptionsBase {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: None,
-            })
-            .await.unwrap();
-    
-        let (device, queue) = adapter.request_device(&Default::default()).await.unwrap();
-    
-        let height: u32 = 13;
-        let width: u32 = 25;
-        let fft_size: u32 = 32;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli {
+    use std::{collections::HashMap, path::PathBuf, str::FromStr};
+
+    use anyhow::{Context, anyhow};
+    use lenia_web::{
+        animal::Animal, colormap::Colormap, cpu_compute::CPUComputeState, fft_compute::FFTComputeState,
+        headless::{Backend, HeadlessSimulation, noise, request_device}, image::encode_png, recorder::Recorder,
+        simulation::SimulationConfig, snapshot::Snapshot, storage_manager::Storage, tracking::{Tracker, segment},
+    };
+
+    /// `--name value` pairs
+    pub struct Options(HashMap<String, String>);
+
+    impl Options {
+        pub fn parse(args: &[String]) -> anyhow::Result<Self> {
+            let mut options = HashMap::new();
+
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                let name = arg.strip_prefix("--").ok_or_else(|| anyhow!("unexpected argument '{}'", arg))?;
+                let value = args.next().ok_or_else(|| anyhow!("--{} needs a value", name))?;
+                options.insert(name.to_string(), value.clone());
+            }
+
+            Ok(Self(options))
+        }
 
-        let signal = vec![vec![0f32; height as usize]; width as usize];
+        fn get<T: FromStr>(&self, name: &str) -> anyhow::Result<Option<T>>
+        where
+            T::Err: std::fmt::Display,
+        {
+            self.0
+                .get(name)
+                .map(|value| value.parse().map_err(|e| anyhow!("invalid --{} '{}': {}", name, value, e)))
+                .transpose()
+        }
 
-        let flattened_signal = signal.into_iter().flatten().collect::<Vec<_>>();
-        let input_buffer = Storage::new(&device, "Input", &flattened_signal);
-        // let fft_buffer = Storage::new_empty(&device, "FFT buffer", (fft_size * fft_size * 2 * 4).into());
+        fn get_or<T: FromStr>(&self, name: &str, default: T) -> anyhow::Result<T>
+        where
+            T::Err: std::fmt::Display,
+        {
+            Ok(self.get(name)?.unwrap_or(default))
+        }
 
-        debug_buffer(&device, &queue, input_buffer.buffer(), |d| display_grid(bytemuck::cast_slice::<_, f32>(&d), height, width)).await;
+        /// `a:b:step`, both ends included, or a single value
+        fn range(&self, name: &str) -> anyhow::Result<Option<Vec<f32>>> {
+            let Some(value) = self.0.get(name) else {
+                return Ok(None);
+            };
+
+            let parts = value
+                .split(':')
+                .map(|part| part.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("invalid --{} '{}': {}", name, value, e))?;
+
+            match parts[..] {
+                [single] => Ok(Some(vec![single])),
+                [start, end, step] if step > 0.0 && start <= end => {
+                    let count = ((end - start) / step + 1e-4).floor() as usize + 1;
+                    Ok(Some((0..count).map(|i| start + i as f32 * step).collect()))
+                }
+                _ => Err(anyhow!("--{} expects a value or start:end:step", name)),
+            }
+        }
 
+        fn size(&self) -> anyhow::Result<(u32, u32)> {
+            let Some(size) = self.0.get("size") else {
+                return Ok((256, 256));
+            };
 
-        let mut encoder = device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: Some("Debug encoder") });
+            let (width, height) = size.split_once('x').ok_or_else(|| anyhow!("--size expects <width>x<height>"))?;
+            Ok((width.parse()?, height.parse()?))
+        }
+    }
 
-        let fft = FFTComputeState::new(&device, &mut encoder, &queue, &input_buffer, width, height);
+    /// the starting cells and parameters of a run
+    struct World {
+        width: u32,
+        height: u32,
+        config: SimulationConfig,
+        cells: Vec<f32>,
+        step: u64,
+    }
 
-        queue.submit(Some(encoder.finish()));
+    fn load_world(options: &Options) -> anyhow::Result<World> {
+        let mut world = if let Some(path) = options.get::<PathBuf>("snapshot")? {
+            let bytes = std::fs::read(&path).with_context(|| format!("could not read {}", path.display()))?;
+            let snapshot = Snapshot::from_bytes(&bytes)?;
+
+            World {
+                width: snapshot.width,
+                height: snapshot.height,
+                config: snapshot.config,
+                cells: snapshot.cells,
+                step: snapshot.step,
+            }
+        } else if let Some(path) = options.get::<PathBuf>("animal")? {
+            let json = std::fs::read_to_string(&path).with_context(|| format!("could not read {}", path.display()))?;
+            let animal = Animal::from_json(&json)?;
+            let (width, height) = options.size()?;
+
+            let mut cells = vec![0.0; (width * height * animal.config.channels) as usize];
+            animal.stamp(&mut cells, width, height, width / 2, height / 2);
+
+            World { width, height, config: animal.config, cells, step: 0 }
+        } else {
+            let (width, height) = options.size()?;
+            let config = SimulationConfig::default();
+            let size = options.get_or("noise-size", width.min(height) / 4)?;
+            let density = options.get_or("density", 0.5)?;
+            let seed = options.get_or("seed", 1)?;
+
+            World {
+                width,
+                height,
+                cells: noise(width, height, config.channels, size, density, seed),
+                config,
+                step: 0,
+            }
+        };
+
+        if let Some(path) = options.get::<PathBuf>("config")? {
+            let json = std::fs::read_to_string(&path).with_context(|| format!("could not read {}", path.display()))?;
+            let config: SimulationConfig = serde_json::from_str(&json)?;
+
+            if config.channels != world.config.channels {
+                return Err(anyhow!(
+                    "the config has {} channels but the world has {}",
+                    config.channels, world.config.channels
+                ));
+            }
+            world.config = config;
+        }
 
+        Ok(world)
+    }
 
-        // let pad = PadWrapState::new(&device, &input_buffer, &fft_buffer, PadWrapUniforms { width, height, size: fft_size });
+    /// `--colormap` names one of the palettes of `colormap.rs`
+    fn colormap(options: &Options) -> anyhow::Result<Colormap> {
+        let Some(name) = options.0.get("colormap") else {
+            return Ok(Colormap::default());
+        };
 
-        // let fft = FFTState::new(&device, &fft_buffer, FFTUniforms {
-        //     size: fft_size,
-        //     num_stages: fft_size.ilog2(),
-        // });
+        let palette = serde_json::from_value(serde_json::Value::String(name.clone()))
+            .map_err(|_| anyhow!("unknown colormap '{}'", name))?;
 
-        // let growth = GrowthState::new(&device, &fft_buffer, &input_buffer, GrowthUniforms {
-        //     time_step: 0,
-        //     m: 0.0,
-        //     s: 0.0,
-        //     fft_size: fft_size,
-        //     height, 
-        //     width,
-        // });
+        Ok(Colormap { palette, ..Default::default() })
+    }
 
-        // let transpose = TransposeState::new(&device, &fft_buffer, TransposeUniforms { size: fft_size });
+    pub async fn run(options: Options) -> anyhow::Result<()> {
+        let world = load_world(&options)?;
+        let backend: Backend = options.get_or("backend", Backend::Gpu)?;
+        let steps: u64 = options.get_or("steps", 100)?;
+        let every: u64 = options.get_or("every", steps.max(1))?.max(1);
+        let out: PathBuf = options.get_or("out", PathBuf::from("out"))?;
+        let format: String = options.get_or("format", "snapshot".to_string())?;
+        let colormap = colormap(&options)?;
+
+        // everything but snapshots is an image sequence
+        let mut recorder = match format.as_str() {
+            "snapshot" => None,
+            format => Some(Recorder::new(every, format.parse()?)),
+        };
+
+        std::fs::create_dir_all(&out).with_context(|| format!("could not create {}", out.display()))?;
+
+        let mut simulation = HeadlessSimulation::new(backend, world.width, world.height, world.config, &world.cells, world.step).await?;
+
+        let mut done = 0;
+        while done < steps {
+            let batch = every.min(steps - done);
+            simulation.run(batch);
+            done += batch;
+
+            let (path, bytes) = match &mut recorder {
+                Some(recorder) => {
+                    let Some(name) = recorder.frame_for_step(done) else {
+                        continue;
+                    };
+                    let rgba = simulation.frame(&colormap).await?;
+                    (out.join(name), recorder.format.encode(simulation.width(), simulation.height(), &rgba))
+                }
+                None => {
+                    let snapshot = simulation.snapshot().await?;
+                    (out.join(format!("{:06}.lenia", snapshot.step)), snapshot.to_bytes(true)?)
+                }
+            };
+
+            std::fs::write(&path, bytes).with_context(|| format!("could not write {}", path.display()))?;
+            println!("step {}: wrote {}", simulation.step_count(), path.display());
+        }
 
-        let mut encoder = device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: Some("Debug encoder") });
-       
-        // pad.run(&mut encoder, &queue);
+        Ok(())
+    }
 
-        // fft.run_forward(&mut encoder, &queue);
-        // transpose.run(&mut encoder, &queue);
-        // fft.run_forward(&mut encoder, &queue);
+    pub async fn sweep(options: Options) -> anyhow::Result<()> {
+        let world = load_world(&options)?;
+        let backend: Backend = options.get_or("backend", Backend::Gpu)?;
+        let steps: u64 = options.get_or("steps", 100)?;
+        let out = options.get::<PathBuf>("out")?;
+        let colormap = colormap(&options)?;
+
+        let first = world.config.kernels[0].clone();
+        let ms = options.range("m")?.unwrap_or(vec![first.m]);
+        let ss = options.range("s")?.unwrap_or(vec![first.s]);
+        let radii = options.range("radius")?.unwrap_or(vec![first.kernel.radius as f32]);
+        let time_steps = options.range("time-step")?.unwrap_or(vec![world.config.time_step]);
+
+        if let Some(out) = &out {
+            std::fs::create_dir_all(out).with_context(|| format!("could not create {}", out.display()))?;
+        }
 
-        // fft.run_inverse(&mut encoder, &queue);
-        // transpose.run(&mut encoder, &queue);
-        // fft.run_inverse(&mut encoder, &queue);
+        let initial_mass: f32 = world.cells.iter().sum();
+
+        println!("run,m,s,radius,time_step,mass,mass_ratio,alive");
+
+        let mut index = 0;
+        for &m in &ms {
+            for &s in &ss {
+                for &radius in &radii {
+                    for &time_step in &time_steps {
+                        let mut config = world.config.clone();
+                        config.time_step = time_step;
+                        for kernel in &mut config.kernels {
+                            kernel.m = m;
+                            kernel.s = s;
+                            kernel.kernel.radius = radius.round() as u32;
+                        }
+
+                        let mut simulation = HeadlessSimulation::new(backend, world.width, world.height, config, &world.cells, world.step).await?;
+                        simulation.run(steps);
+                        let cells = simulation.cells().await?;
+
+                        let mass: f32 = cells.iter().sum();
+                        let alive = cells.iter().filter(|&&c| c > 0.0).count() as f32 / cells.len() as f32;
+
+                        println!(
+                            "{},{},{},{},{},{:.3},{:.4},{:.4}",
+                            index, m, s, radius.round(), time_step, mass, mass / initial_mass.max(f32::EPSILON), alive
+                        );
+
+                        if let Some(out) = &out {
+                            let rgba = simulation.frame(&colormap).await?;
+                            let path = out.join(format!("run_{:04}.png", index));
+                            std::fs::write(&path, encode_png(world.width, world.height, &rgba))
+                                .with_context(|| format!("could not write {}", path.display()))?;
+                        }
+
+                        index += 1;
+                    }
+                }
+            }
+        }
 
-        // growth.run(&mut encoder, &queue);
+        Ok(())
+    }
 
-        fft.run(&mut encoder, &queue);
+    pub async fn track(options: Options) -> anyhow::Result<()> {
+        let world = load_world(&options)?;
+        let backend: Backend = options.get_or("backend", Backend::Gpu)?;
+        let steps: u64 = options.get_or("steps", 100)?;
+        let every: u64 = options.get_or("every", 1)?.max(1);
+        let threshold: f32 = options.get_or("threshold", 0.1)?;
+
+        let mut simulation = HeadlessSimulation::new(backend, world.width, world.height, world.config, &world.cells, world.step).await?;
+        let channels = simulation.config().channels;
+        let mut tracker = Tracker::new();
+
+        println!("step,time,id,mass,cells,x,y,vx,vy,orientation");
+
+        let mut done = 0;
+        loop {
+            let cells = simulation.cells().await?;
+            let objects = segment(&cells, world.width, world.height, channels, threshold);
+
+            for tracked in tracker.update(objects, world.width, world.height, simulation.step_count(), simulation.time()) {
+                let object = &tracked.object;
+                println!(
+                    "{},{:.4},{},{:.3},{},{:.3},{:.3},{:.4},{:.4},{:.4}",
+                    simulation.step_count(), simulation.time(), tracked.id, object.mass, object.cells,
+                    object.center[0], object.center[1], tracked.velocity[0], tracked.velocity[1], object.orientation
+                );
+            }
+
+            if done >= steps {
+                break;
+            }
+
+            let batch = every.min(steps - done);
+            simulation.run(batch);
+            done += batch;
+        }
 
-        queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
 
-        let mut encoder = device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: Some("Debug encoder") });
-        
-        fft.run(&mut encoder, &queue);
+    /// the old debug harness, checks `FFTComputeState` against `CPUComputeState` step by step
+    pub async fn compare(options: Options) -> anyhow::Result<()> {
+        let software = options.get_or("backend", Backend::Gpu)? == Backend::Software;
+        let (device, queue) = request_device(software).await?;
 
-        queue.submit(Some(encoder.finish()));
-        let mut encoder = device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: Some("Debug encoder") });
+        let (width, height) = if options.0.contains_key("size") { options.size()? } else { (96, 64) };
+        let steps = options.get_or("steps", 10)?;
 
-        fft.run(&mut encoder, &queue);
+        let config = SimulationConfig::default();
 
-        queue.submit(Some(encoder.finish()));
-        let mut encoder = device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: Some("Debug encoder") });
+        // same grid on both sides, a sparse square in the middle so the growth neither dies out nor saturates at once
+        let size = options.get_or("noise-size", 4 * config.max_radius())?;
+        let density = options.get_or("density", 0.5)?;
+        let mut grid = noise(width, height, config.channels, size, density, options.get_or("seed", 1)?);
 
-        fft.run(&mut encoder, &queue);
+        let input_buffer = Storage::new(&device, "Input", &grid);
 
-        queue.submit(Some(encoder.finish()));
         let mut encoder = device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: Some("Debug encoder") });
+        let fft = FFTComputeState::new(&device, &mut encoder, &queue, &input_buffer, width, height, config.clone());
+        queue.submit(Some(encoder.finish()));
 
-        fft.run(&mut encoder, &queue);
+        let cpu = CPUComputeState::new(width, height, config)?;
+        let mut growing_steps = 0;
 
-        queue.submit(Some(encoder.finish()));
+        for step in 0..steps {
+            let mut encoder = device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: Some("Debug encoder") });
+            fft.run(&mut encoder, &queue);
+            queue.submit(Some(encoder.finish()));
 
-        // debug_buffer(&device, &queue, fft_buffer.buffer(), |d| display_grid(&bytemuck::cast_slice::<_, [f32; 2]>(&d).iter().map(|v| { let mut vc = v.to_owned(); vc[0] /= (fft_size * fft_size) as f32; vc}).collect::<Vec<_>>(), fft_size, fft_size)).await;
+            // growth bottoms out at -1 far from m, an empty or saturated grid then decays the same way on both sides
+            // whatever the convolution did
+            let growing = cpu.config().kernels
+                .iter()
+                .zip(cpu.potential(&grid))
+                .any(|(kernel, sums)| sums.iter().any(|&u| cpu.config().growth_function.eval(u, kernel.m, kernel.s) > -1.0));
+            growing_steps += growing as u32;
 
-        debug_buffer(&device, &queue, input_buffer.buffer(), |d| display_grid(bytemuck::cast_slice::<_, f32>(&d), width, height)).await;
+            cpu.step(&mut grid);
 
-    }
+            let gpu_grid = input_buffer.read(&device, &queue).await?;
+            let max_diff = bytemuck::cast_slice::<_, f32>(&gpu_grid)
+                .iter()
+                .zip(&grid)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0f32, f32::max);
 
-#[cfg(not(target_arch = "wasm32"))]  
-pub async fn debug_buffer(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        buffer: &wgpu::Buffer,
-        display: impl FnOnce(&[u8])
-    ) {
-        let temp_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("temp"),
-            size: buffer.size(),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
-    
-        let mut encoder = device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor { label: Some("Debug encoder") });
-    
-        encoder.copy_buffer_to_buffer(buffer, 0, &temp_buffer, 0, buffer.size());
-        queue.submit(Some(encoder.finish()));
-    
-        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
-        
-        temp_buffer.map_async(wgpu::MapMode::Read, .., move |result| {
-            tokio::spawn(async move { tx.send(result).await.unwrap()});
-        });
-    
-        println!("wating for poll");
-        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
-    
-        println!("wating for flag");
-        rx.recv().await.unwrap().unwrap();
-    
-        {
-            let output_data = temp_buffer.get_mapped_range(..);
-            display(&output_data);
+            println!("step {step}: max diff {max_diff:e}{}", if growing { "" } else { " (nothing grows)" });
         }
-        temp_buffer.unmap();
+
+        if growing_steps == 0 {
+            return Err(anyhow!("no cell came near the growth center in {steps} steps, the diff doesn't test the convolution"));
+        }
+
+        if width * height <= 32 * 32 {
+            display_grid(&grid, width, height);
+        }
+
+        Ok(())
     }
 
-#[cfg(not(target_arch = "wasm32"))]
-fn display_grid<D: std::fmt::Debug>(data: &[D], width: u32, height: u32) {
-
-    let max_len = data
-        .iter()
-        .map(|v| format!("{v:.3?}").len())
-        .max()
-        .unwrap_or(0);
-
-    for i in 0..height {
-        for j in 0..width {
-            let idx = (i * width + j) as usize;
-            print!("{:<w$} ", format!("{:.3?}", data[idx]), w = max_len.max(4));
+    fn display_grid<D: std::fmt::Debug>(data: &[D], width: u32, height: u32) {
+        let max_len = data
+            .iter()
+            .map(|v| format!("{v:.3?}").len())
+            .max()
+            .unwrap_or(0);
+
+        for i in 0..height {
+            for j in 0..width {
+                let idx = (i * width + j) as usize;
+                print!("{:<w$} ", format!("{:.3?}", data[idx]), w = max_len.max(4));
+            }
+            println!();
         }
-        println!("");
     }
 }
@@ -1,112 +1,108 @@
-use crate::{storage_manager::Storage, uniforms_manager::Uniforms};
+//! Counter based random numbers shared by the gpu and the cpu.
+//!
+//! Every value is a hash of the seed and the cell it belongs to, so a seed paints the same cells
+//! whatever the world size, backend or order they are generated in. random.wgsl holds the gpu side.
 
-pub struct RandomState {
-    pipeline: wgpu::ComputePipeline,
-    bind_group_layout: wgpu::BindGroupLayout,
-    bind_group: wgpu::BindGroup,
-    pub uniforms: Uniforms<RandomUniforms>,
+/// the pcg hash from <https://jcgt.org/published/0009/03/02/>
+pub fn pcg(v: u32) -> u32 {
+    let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
 }
 
-#[derive(Copy, Clone, Debug, Default, encase::ShaderType)]
-pub struct RandomUniforms {
-    pub height: u32,
-    pub width: u32,
-    pub x: u32,
-    pub y: u32,
-    pub seed: u32,
-    pub density: f32,
-    pub use_brush: u32,
-    pub size: u32,
+/// uniform in [0, 1), identical to `cell_random` in random.wgsl
+pub fn cell_random(seed: u32, x: u32, y: u32, channel: u32) -> f32 {
+    let hash = pcg(pcg(pcg(pcg(seed).wrapping_add(x)).wrapping_add(y)).wrapping_add(channel));
+    (hash >> 8) as f32 / (1 << 24) as f32
 }
 
-impl RandomState {
-    fn create_bind_group(
-        device: &wgpu::Device,
-        bind_group_layout: &wgpu::BindGroupLayout,
-        grid: &Storage,
-        uniforms: &Uniforms<RandomUniforms>
-    ) -> wgpu::BindGroup {
-        device.create_bind_group(&wgpu::BindGroupDescriptor { 
-            label: Some("Randomness Bind Group"), 
-            layout: &bind_group_layout, 
-            entries: &[
-                uniforms.bind_group_entry(0),
-                grid.bind_group_entry(1),
-            ]
-        })
-    }
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::{headless::{noise, request_device}, storage_manager::Storage};
 
-    pub fn new(
-        device: &wgpu::Device,
-        grid: &Storage,
-        mut uniforms: Uniforms<RandomUniforms>,
-    ) -> Self {
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Randomness Bind Group Layout"),
-            entries: &[
-                uniforms.layout_entry(0, wgpu::ShaderStages::COMPUTE),
-                grid.layout_entry(1, wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE, false),
-            ],
-        });
+    use super::*;
+
+    #[test]
+    fn noise_does_not_depend_on_world_size() {
+        let (size, channels) = (16, 2);
+        let square = |width: u32, height: u32| {
+            let grid = noise(width, height, channels, size, 0.5, 9);
+            let (left, top) = ((width - size) / 2, (height - size) / 2);
+
+            grid.chunks_exact((width * height) as usize)
+                .flat_map(|plane| (0..size).flat_map(move |y| (0..size).map(move |x| plane[((top + y) * width + left + x) as usize])))
+                .collect::<Vec<_>>()
+        };
 
-        uniforms.use_brush = 1;
+        let reference = square(16, 16);
+        assert!(reference.iter().any(|c| *c > 0.0));
+        for (width, height) in [(40, 30), (101, 64), (17, 250)] {
+            assert_eq!(square(width, height), reference, "{}x{}", width, height);
+        }
+    }
 
-        let bind_group = Self::create_bind_group(device, &bind_group_layout, grid, &uniforms);
+    /// random.wgsl against this file, bit for bit, on the first adapter wgpu finds
+    #[tokio::test]
+    async fn gpu_matches_cpu() {
+        let Ok((device, queue)) = request_device(false).await else {
+            eprintln!("skipping, no adapter");
+            return;
+        };
 
-        let shader = device.create_shader_module(wgpu::include_wgsl!("random.wgsl"));
+        const WIDTH: u32 = 37;
+        const COUNT: u32 = WIDTH * 29;
+        // coordinates close to u32::MAX so the additions wrap on both sides
+        const OFFSET: u32 = u32::MAX - 20;
+        let (seed, channel) = (0xdead_beef, 3);
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Randomness Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("random test"),
+            source: wgpu::ShaderSource::Wgsl(format!(
+                "{}
+                @group(0) @binding(0) var<storage, read_write> values: array<u32>;
+
+                @compute @workgroup_size(64)
+                fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+                    if id.x >= {COUNT}u {{
+                        return;
+                    }}
+                    let x = id.x % {WIDTH}u + {OFFSET}u;
+                    let y = id.x / {WIDTH}u;
+                    values[id.x] = bitcast<u32>(cell_random({seed}u, x, y, {channel}u));
+                }}",
+                include_str!("random.wgsl"),
+            ).into()),
         });
 
         let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Randomness Pipeline"),
-            layout: Some(&pipeline_layout),
+            label: Some("random test"),
+            layout: None,
             module: &shader,
             entry_point: None,
             compilation_options: Default::default(),
             cache: None,
         });
 
-        Self {
-            pipeline,
-            bind_group_layout,
-            bind_group,
-            uniforms,
-        }
-    }
-
-    pub fn recreate_bind_groups(
-        &mut self,
-        device: &wgpu::Device,
-        grid: &Storage,
-    ) {
-       self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, grid, &self.uniforms)
-    }
-
-    pub fn run(
-        &mut self,
-        encoder: &mut wgpu::CommandEncoder,
-        queue: &wgpu::Queue,
-        x: u32,
-        y: u32,
-    ) {
-        self.uniforms.x = x;
-        self.uniforms.y = y;
-        self.uniforms.write(queue);
-
-        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-            label: Some("Randomness Compute Pass"),
-            timestamp_writes: None,
+        let values = Storage::new(&device, "Random", &[0u32; COUNT as usize]);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[values.bind_group_entry(0)],
         });
 
-        pass.set_pipeline(&self.pipeline);
-        pass.set_bind_group(0, &self.bind_group, &[]);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(COUNT.div_ceil(64), 1, 1);
+        }
+        queue.submit(Some(encoder.finish()));
 
-        let workgroups_x = (&self.uniforms.width + 15) / 16;
-        let workgroups_y = (&self.uniforms.height + 15) / 16;
-        pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+        let gpu: Vec<u32> = bytemuck::pod_collect_to_vec(&values.read(&device, &queue).await.unwrap());
+        for (i, bits) in gpu.into_iter().enumerate() {
+            let (x, y) = ((i as u32 % WIDTH).wrapping_add(OFFSET), i as u32 / WIDTH);
+            assert_eq!(f32::from_bits(bits), cell_random(seed, x, y, channel), "cell ({}, {})", x, y);
+        }
     }
 }
@@ -1,48 +1,16 @@
-struct RandomnessUniforms {
-    height: u32,
-    width: u32,
-    x: u32,
-    y: u32,
-    seed: u32,
-    density: f32,
-    use_brush: u32,
-    size: u32,
-}
-
-@group(0) @binding(0) var<uniform> uniforms: RandomnessUniforms;
-@group(0) @binding(1) var<storage, read_write> output: array<f32>;
+// Counter based random numbers, prepended to the shaders that need them.
+// random.rs has the matching cpu implementation, the two have to stay bit for bit identical.
 
-// https://www.reedbeta.com/blog/quick-and-easy-gpu-random-numbers-in-d3d11/
-fn wang_hash(seed: u32) -> u32 {
-    var s = seed;
-    s = (s ^ 61u) ^ (s >> 16u);
-    s = s * 9u;
-    s = s ^ (s >> 4u);
-    s = s * 0x27d4eb2du;
-    s = s ^ (s >> 15u);
-    return s;
+// https://jcgt.org/published/0009/03/02/ (pcg hash)
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
 }
 
-@compute
-@workgroup_size(16, 16)
-fn randomize(
-    @builtin(global_invocation_id) global_id: vec3<u32>,
-) {
-    let x = global_id.x;
-    let y = global_id.y;
-    
-    if (x >= uniforms.width || y >= uniforms.height) {
-        return;
-    }
-
-    if (uniforms.use_brush == 1 && !(
-        i32(uniforms.x) - i32(uniforms.size) <= i32(x) &&  uniforms.x + uniforms.size >= x &&
-        i32(uniforms.y) - i32(uniforms.size) <= i32(y) &&  uniforms.y + uniforms.size >= y
-    )) {
-        return;
-    }
-
-    let index = y * uniforms.width + x;
-    let combined = x + y * uniforms.width + uniforms.seed * uniforms.width * uniforms.height;
-    output[index] = (f32(wang_hash(combined)) / 4294967296.0) * uniforms.density;
-}
\ No newline at end of file
+// uniform in [0, 1), keyed by the seed and the absolute cell coordinates only
+fn cell_random(seed: u32, x: u32, y: u32, channel: u32) -> f32 {
+    let hash = pcg(pcg(pcg(pcg(seed) + x) + y) + channel);
+    // 24 bits fit exactly in an f32, so the gpu and the cpu agree
+    return f32(hash >> 8u) / 16777216.0;
+}
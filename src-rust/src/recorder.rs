@@ -0,0 +1,36 @@
+use crate::image::ImageFormat;
+
+/// Picks every `every`th step of a run for an image sequence and numbers the frames
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recorder {
+    pub every: u64,
+    pub format: ImageFormat,
+    frames: u32,
+}
+
+impl Recorder {
+    pub fn new(every: u64, format: ImageFormat) -> Self {
+        Self {
+            every: every.max(1),
+            format,
+            frames: 0,
+        }
+    }
+
+    /// file name of the next frame, `frame_000042.png` for example, if `step` is part of the sequence
+    pub fn frame_for_step(&mut self, step: u64) -> Option<String> {
+        if !step.is_multiple_of(self.every) {
+            return None;
+        }
+
+        let name = format!("frame_{:06}.{}", self.frames, self.format.extension());
+        self.frames += 1;
+
+        Some(name)
+    }
+
+    /// number of frames handed out so far
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+}
@@ -0,0 +1,82 @@
+struct ReductionUniforms {
+    // elements offset + i * stride of the input, for i below len
+    len: u32,
+    offset: u32,
+    stride: u32,
+    op: u32,
+    // squares the elements as they are loaded, the first pass of a sum of squares
+    square: u32,
+    // where the first workgroup writes its result
+    out_offset: u32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: ReductionUniforms;
+@group(0) @binding(1) var<storage, read> input: array<f32>;
+@group(0) @binding(2) var<storage, read_write> output: array<f32>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+// every thread loads two elements
+const PER_WORKGROUP: u32 = 512u;
+
+const OP_SUM: u32 = 0u;
+const OP_MIN: u32 = 1u;
+const OP_MAX: u32 = 2u;
+
+const F32_MAX: f32 = 3.40282347e38;
+
+var<workgroup> values: array<f32, WORKGROUP_SIZE>;
+
+fn identity() -> f32 {
+    switch uniforms.op {
+        case OP_MIN: { return F32_MAX; }
+        case OP_MAX: { return -F32_MAX; }
+        default: { return 0.0; }
+    }
+}
+
+fn combine(a: f32, b: f32) -> f32 {
+    switch uniforms.op {
+        case OP_MIN: { return min(a, b); }
+        case OP_MAX: { return max(a, b); }
+        default: { return a + b; }
+    }
+}
+
+fn load(i: u32) -> f32 {
+    if (i >= uniforms.len) {
+        return identity();
+    }
+
+    let value = input[uniforms.offset + i * uniforms.stride];
+    return select(value, value * value, uniforms.square != 0u);
+}
+
+@compute
+@workgroup_size(WORKGROUP_SIZE)
+fn reduce(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    // large inputs are dispatched over two dimensions
+    let group = workgroup_id.y * workgroups.x + workgroup_id.x;
+    if (group >= max(1u, (uniforms.len + PER_WORKGROUP - 1u) / PER_WORKGROUP)) {
+        return;
+    }
+
+    let base = group * PER_WORKGROUP + local;
+    values[local] = combine(load(base), load(base + WORKGROUP_SIZE));
+
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if (local < stride) {
+            values[local] = combine(values[local], values[local + stride]);
+        }
+        workgroupBarrier();
+    }
+
+    if (local == 0u) {
+        output[uniforms.out_offset + group] = values[0];
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{storage_manager::Storage, uniforms_manager::Uniforms};
+
+/// elements reduced by one workgroup, see reduction.wgsl
+const PER_WORKGROUP: u32 = 512;
+/// most workgroups wgpu allows along one dimension
+const MAX_WORKGROUPS: u32 = 65535;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum ReduceOp {
+    Sum = 0,
+    Min = 1,
+    Max = 2,
+    SumOfSquares = 3,
+}
+
+/// The elements `offset + i * stride` for i in 0..len of an f32 buffer.
+/// A stride of 2 picks the real or imaginary parts of a complex fft buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elements {
+    pub offset: u32,
+    pub stride: u32,
+    pub len: u32,
+}
+
+impl Elements {
+    pub fn contiguous(offset: u32, len: u32) -> Self {
+        Self { offset, stride: 1, len }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, encase::ShaderType)]
+struct ReductionUniforms {
+    len: u32,
+    offset: u32,
+    stride: u32,
+    op: u32,
+    square: u32,
+    out_offset: u32,
+}
+
+/// Reduces f32 buffers to a single value with a tree reduction per workgroup,
+/// repeated over the partial results until one is left
+pub struct Reducer {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Reducer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Reduction Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1, true),
+                storage_entry(2, false),
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("reduction.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Reduction Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Reduction Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: None,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    /// Records one reduction per op over the same elements of `input`.
+    /// The returned buffer holds the results in the order of `ops` once the encoder has run.
+    pub fn record(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &Storage,
+        elements: Elements,
+        ops: &[ReduceOp],
+    ) -> Storage {
+        let result = Storage::new_empty(device, "Reduction Result", (ops.len().max(1) * 4) as u64);
+
+        // the passes ping pong between two scratch buffers sized for the first two passes
+        let first = elements.len.div_ceil(PER_WORKGROUP).max(1);
+        let second = first.div_ceil(PER_WORKGROUP).max(1);
+        let scratch = [
+            Storage::new_empty(device, "Reduction Scratch", first as u64 * 4),
+            Storage::new_empty(device, "Reduction Scratch", second as u64 * 4),
+        ];
+
+        // uniforms are created per pass rather than rewritten, writes would all land before the encoder runs
+        let mut passes = Vec::new();
+
+        for (slot, &op) in ops.iter().enumerate() {
+            let mut source = input;
+            let mut uniforms = ReductionUniforms {
+                len: elements.len,
+                offset: elements.offset,
+                stride: elements.stride,
+                op: if op == ReduceOp::SumOfSquares { ReduceOp::Sum as u32 } else { op as u32 },
+                square: (op == ReduceOp::SumOfSquares) as u32,
+                out_offset: 0,
+            };
+
+            for pass in 0.. {
+                let workgroups = uniforms.len.div_ceil(PER_WORKGROUP).max(1);
+                let last = workgroups == 1;
+
+                let destination = if last {
+                    uniforms.out_offset = slot as u32;
+                    &result
+                } else {
+                    &scratch[pass % 2]
+                };
+
+                let uniforms_buffer = Uniforms::new(device, "Reduction", uniforms);
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Reduction Bind Group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        uniforms_buffer.bind_group_entry(0),
+                        source.bind_group_entry(1),
+                        destination.bind_group_entry(2),
+                    ],
+                });
+                passes.push((bind_group, workgroups));
+
+                if last {
+                    break;
+                }
+
+                source = destination;
+                uniforms = ReductionUniforms {
+                    len: workgroups,
+                    offset: 0,
+                    stride: 1,
+                    square: 0,
+                    ..uniforms
+                };
+            }
+        }
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Reduction Compute Pass"),
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+
+        for (bind_group, workgroups) in &passes {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch_workgroups(
+                (*workgroups).min(MAX_WORKGROUPS),
+                workgroups.div_ceil(MAX_WORKGROUPS),
+                1,
+            );
+        }
+
+        drop(pass);
+
+        result
+    }
+
+    /// reduces the elements once everything submitted before has run, and resolves with one value per op without blocking
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        input: &Storage,
+        elements: Elements,
+        ops: &[ReduceOp],
+    ) -> impl Future<Output = anyhow::Result<Vec<f32>>> + use<> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Reduction Encoder") });
+        let result = self.record(device, &mut encoder, input, elements, ops);
+        queue.submit(Some(encoder.finish()));
+
+        let bytes = result.read(device, queue);
+        let len = ops.len();
+
+        async move {
+            let bytes = bytes.await?;
+            Ok(bytemuck::pod_collect_to_vec::<u8, f32>(&bytes)[..len].to_vec())
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::{headless::request_device, random::cell_random};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn matches_cpu_reduction() {
+        let Ok((device, queue)) = request_device(false).await else {
+            eprintln!("skipping, no adapter");
+            return;
+        };
+        let reducer = Reducer::new(&device);
+
+        let values = (0..600_003).map(|i| cell_random(4, i, 0, 0) * 2.0 - 1.0).collect::<Vec<_>>();
+        let input = Storage::new(&device, "Input", &values);
+        let ops = [ReduceOp::Sum, ReduceOp::Min, ReduceOp::Max, ReduceOp::SumOfSquares];
+
+        // not multiples of 512, up to three passes, and every other value like the real parts of an fft buffer
+        let cases = [
+            Elements::contiguous(0, 1),
+            Elements::contiguous(3, 511),
+            Elements::contiguous(0, 513),
+            Elements::contiguous(7, 1000),
+            Elements::contiguous(1, 300_001),
+            Elements { offset: 1, stride: 2, len: 300_001 },
+        ];
+
+        for elements in cases {
+            let selected = (0..elements.len)
+                .map(|i| values[(elements.offset + i * elements.stride) as usize] as f64)
+                .collect::<Vec<_>>();
+
+            let sum: f64 = selected.iter().sum();
+            let min = selected.iter().copied().fold(f64::MAX, f64::min);
+            let max = selected.iter().copied().fold(f64::MIN, f64::max);
+            let sum_of_squares: f64 = selected.iter().map(|v| v * v).sum();
+
+            let gpu = reducer.read(&device, &queue, &input, elements, &ops).await.unwrap();
+
+            // a tree of f32 additions, the error grows with the sum of the magnitudes
+            let tolerance = 1e-6 * selected.iter().map(|v| v.abs()).sum::<f64>().max(1.0);
+            assert!((gpu[0] as f64 - sum).abs() < tolerance, "{:?}: sum {} != {}", elements, gpu[0], sum);
+            assert_eq!(gpu[1] as f64, min, "{:?}: min", elements);
+            assert_eq!(gpu[2] as f64, max, "{:?}: max", elements);
+            assert!((gpu[3] as f64 - sum_of_squares).abs() < tolerance, "{:?}: sum of squares {} != {}", elements, gpu[3], sum_of_squares);
+        }
+    }
+}
@@ -1,11 +1,28 @@
 struct RenderUniforms { 
     height: u32,
     width: u32,
+    channels: u32,
+    view_width: u32,
+    view_height: u32,
+    zoom: f32,
+    center_x: f32,
+    center_y: f32,
+    filter_mode: u32,
+    render_mode: u32,
+    layer: u32,
+    range_min: f32,
+    range_max: f32,
+    highlight_out_of_range: u32,
+    fft_width: u32,
+    fft_height: u32,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: RenderUniforms;
 @group(0) @binding(1) var<storage, read> colors: array<vec3<f32>>;
 @group(0) @binding(2) var<storage, read> grid: array<f32>;
+// fft sized planes, see `FFTBuffers`. The potential is real, with rows padded as in fft.wgsl
+@group(0) @binding(3) var<storage, read> potential: array<f32>;
+@group(0) @binding(4) var<storage, read> fields: array<vec2<f32>>;
 
 @vertex fn vs(
     @builtin(vertex_index) vertexIndex : u32
@@ -20,22 +37,154 @@ struct RenderUniforms {
     return vec4f(pos[vertexIndex], 0.0, 1.0);
 }
 
+const FILTER_NEAREST: u32 = 0u;
+const FILTER_BILINEAR: u32 = 1u;
+const FILTER_BICUBIC: u32 = 2u;
+
+const MODE_STATE: u32 = 0u;
+const MODE_POTENTIAL: u32 = 1u;
+const MODE_GROWTH: u32 = 2u;
+const MODE_DELTA: u32 = 3u;
+
+const BACKGROUND: vec3<f32> = vec3<f32>(0.05, 0.05, 0.08);
+const OUT_OF_RANGE: vec3<f32> = vec3<f32>(1.0, 0.0, 1.0);
+
+// value of the field shown by the render mode, clamped to the edge of the world
+fn cell(x: i32, y: i32, layer: u32) -> f32 {
+    let cx = u32(clamp(x, 0, i32(uniforms.width) - 1));
+    let cy = u32(clamp(y, 0, i32(uniforms.height) - 1));
+
+    let plane_size = uniforms.fft_width * uniforms.fft_height;
+    let fft_idx = cy * uniforms.fft_width + cx;
+
+    switch uniforms.render_mode {
+        case MODE_POTENTIAL: {
+            let stride = 2u * (uniforms.fft_width / 2u + 1u);
+            let potential_plane_size = uniforms.fft_height * stride;
+            let plane = min(layer, arrayLength(&potential) / potential_plane_size - 1u);
+            return potential[plane * potential_plane_size + cy * stride + cx] / f32(plane_size);
+        }
+        case MODE_GROWTH: {
+            let plane = min(layer, arrayLength(&fields) / plane_size - 1u);
+            return fields[plane * plane_size + fft_idx].x;
+        }
+        case MODE_DELTA: {
+            let plane = min(layer, arrayLength(&fields) / plane_size - 1u);
+            return fields[plane * plane_size + fft_idx].y;
+        }
+        default: {
+            return grid[layer * uniforms.width * uniforms.height + cy * uniforms.width + cx];
+        }
+    }
+}
+
+// cool to warm through a light grey, t in [0, 1]
+fn diverging(t: f32) -> vec3<f32> {
+    let cool = vec3<f32>(0.230, 0.299, 0.754);
+    let middle = vec3<f32>(0.865, 0.865, 0.865);
+    let warm = vec3<f32>(0.706, 0.016, 0.150);
+
+    if (t < 0.5) {
+        return mix(cool, middle, t * 2.0);
+    }
+    return mix(middle, warm, t * 2.0 - 1.0);
+}
+
+// position of a value in the configured range, 0 and 1 at its ends
+fn normalize_value(value: f32) -> f32 {
+    return (value - uniforms.range_min) / (uniforms.range_max - uniforms.range_min);
+}
+
+fn is_out_of_range(t: f32) -> bool {
+    return uniforms.highlight_out_of_range != 0u && (t < 0.0 || t > 1.0);
+}
+
+// catmull-rom weights for the 4 taps around t
+fn cubic_weights(t: f32) -> vec4<f32> {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    return vec4<f32>(
+        -0.5 * t3 + t2 - 0.5 * t,
+        1.5 * t3 - 2.5 * t2 + 1.0,
+        -1.5 * t3 + 2.0 * t2 + 0.5 * t,
+        0.5 * t3 - 0.5 * t2,
+    );
+}
+
+// value of the world at a continuous position in cells
+fn sample_world(p: vec2<f32>, channel: u32) -> f32 {
+    switch uniforms.filter_mode {
+        case FILTER_BILINEAR: {
+            // cell centers sit at .5
+            let q = p - 0.5;
+            let i = vec2<i32>(floor(q));
+            let t = fract(q);
+
+            let top = mix(cell(i.x, i.y, channel), cell(i.x + 1, i.y, channel), t.x);
+            let bottom = mix(cell(i.x, i.y + 1, channel), cell(i.x + 1, i.y + 1, channel), t.x);
+            return mix(top, bottom, t.y);
+        }
+        case FILTER_BICUBIC: {
+            let q = p - 0.5;
+            let i = vec2<i32>(floor(q));
+            let wx = cubic_weights(fract(q.x));
+            let wy = cubic_weights(fract(q.y));
+
+            var value = 0.0;
+            for (var dy = 0; dy < 4; dy++) {
+                var row = 0.0;
+                for (var dx = 0; dx < 4; dx++) {
+                    row += wx[dx] * cell(i.x + dx - 1, i.y + dy - 1, channel);
+                }
+                value += wy[dy] * row;
+            }
+            return value;
+        }
+        default: {
+            return cell(i32(floor(p.x)), i32(floor(p.y)), channel);
+        }
+    }
+}
+
 @fragment fn fs(@builtin(position) pos: vec4<f32>) -> @location(0) vec4f {
-    let x = u32(pos.x);
-    let y = u32(pos.y);
+    // the camera looks at `center`, at zoom 1 the whole world is stretched over the canvas
+    let view = vec2<f32>(f32(uniforms.view_width), f32(uniforms.view_height));
+    let world = vec2<f32>(f32(uniforms.width), f32(uniforms.height));
+    let scale = view / world * uniforms.zoom;
+    let p = vec2<f32>(uniforms.center_x, uniforms.center_y) + (pos.xy - view / 2.0) / scale;
+
+    if (p.x < 0.0 || p.y < 0.0 || p.x >= world.x || p.y >= world.y) {
+        return vec4<f32>(BACKGROUND, 1.0);
+    }
 
-    // return vec4<f32>(1.0, (f32(x) / f32(uniforms.width)), (f32(y) / f32(uniforms.height)), 1.0);
+    if (uniforms.render_mode != MODE_STATE) {
+        let t = normalize_value(sample_world(p, uniforms.layer));
 
-    if (x >= uniforms.width || y >= uniforms.height) {
-        return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+        if (is_out_of_range(t)) {
+            return vec4<f32>(OUT_OF_RANGE, 1.0);
+        }
+        return vec4<f32>(diverging(clamp(t, 0.0, 1.0)), 1.0);
     }
 
-    let val = grid[y * uniforms.width + x];
+    // multiple channels are shown directly as rgb, the first three at most
+    if (uniforms.channels > 1u) {
+        var rgb = vec3<f32>(0.0, 0.0, 0.0);
+        for (var c = 0u; c < min(uniforms.channels, 3u); c++) {
+            let t = normalize_value(sample_world(p, c));
+            if (is_out_of_range(t)) {
+                return vec4<f32>(OUT_OF_RANGE, 1.0);
+            }
+            rgb[c] = clamp(t, 0.0, 1.0);
+        }
+        return vec4<f32>(rgb, 1.0);
+    }
+
+    let t = normalize_value(sample_world(p, 0u));
 
-    if (val < 0.0 || val > 1.0) {
-        return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+    if (is_out_of_range(t)) {
+        return vec4<f32>(OUT_OF_RANGE, 1.0);
     }
 
-    let color_index = u32(val * 255);
+    let color_index = u32(clamp(t, 0.0, 1.0) * 255);
     return vec4<f32>(colors[color_index], 1.0);
-}
\ No newline at end of file
+}
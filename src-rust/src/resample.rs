@@ -0,0 +1,108 @@
+use crate::{storage_manager::Storage, uniforms_manager::Uniforms};
+
+/// How the old grid is mapped onto a grid of a different size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum ResampleMode {
+    /// keeps cells at their size, centered, cutting off or padding the edges
+    #[default]
+    Crop = 0,
+    /// stretches the grid, nearest cell
+    Nearest = 1,
+    /// stretches the grid, interpolating between cells
+    Bilinear = 2,
+    /// repeats the old grid to fill the new one
+    Tile = 3,
+}
+
+pub struct ResampleState {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pub uniforms: Uniforms<ResampleUniforms>,
+}
+
+#[derive(Clone, Copy, Debug, Default, encase::ShaderType)]
+pub struct ResampleUniforms {
+    pub from_width: u32,
+    pub from_height: u32,
+    pub to_width: u32,
+    pub to_height: u32,
+    pub channels: u32,
+    pub mode: u32,
+}
+
+impl ResampleState {
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Storage,
+        uniforms: Uniforms<ResampleUniforms>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("resample.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Resample Bind Group Layout"),
+            entries: &[
+                uniforms.layout_entry(0, wgpu::ShaderStages::COMPUTE),
+                grid.layout_entry(1, wgpu::ShaderStages::COMPUTE, true),
+                grid.layout_entry(2, wgpu::ShaderStages::COMPUTE, false),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Resample Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Resample Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: None,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            uniforms,
+        }
+    }
+
+    /// copies `from` into `to` using the sizes and mode in the uniforms.
+    /// Both grids change on every resize, so the bind group is built here instead of being kept around
+    pub fn run(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        from: &Storage,
+        to: &Storage,
+    ) {
+        self.uniforms.write(queue);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Resample Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                self.uniforms.bind_group_entry(0),
+                from.bind_group_entry(1),
+                to.bind_group_entry(2),
+            ],
+        });
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Resample Compute Pass"),
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+
+        let workgroups_x = self.uniforms.to_width.div_ceil(16);
+        let workgroups_y = self.uniforms.to_height.div_ceil(16);
+        pass.dispatch_workgroups(workgroups_x, workgroups_y, self.uniforms.channels);
+    }
+}
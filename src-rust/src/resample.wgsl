@@ -0,0 +1,72 @@
+struct ResampleUniforms {
+    from_width: u32,
+    from_height: u32,
+    to_width: u32,
+    to_height: u32,
+    channels: u32,
+    mode: u32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: ResampleUniforms;
+@group(0) @binding(1) var<storage, read> from_grid: array<f32>;
+@group(0) @binding(2) var<storage, read_write> to_grid: array<f32>;
+
+const RESAMPLE_CROP: u32 = 0u;
+const RESAMPLE_NEAREST: u32 = 1u;
+const RESAMPLE_BILINEAR: u32 = 2u;
+const RESAMPLE_TILE: u32 = 3u;
+
+fn sample(x: u32, y: u32, channel: u32) -> f32 {
+    return from_grid[channel * uniforms.from_width * uniforms.from_height + y * uniforms.from_width + x];
+}
+
+@compute
+@workgroup_size(16, 16)
+fn resample(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let x = global_id.x;
+    let y = global_id.y;
+    let channel = global_id.z;
+
+    if (x >= uniforms.to_width || y >= uniforms.to_height || channel >= uniforms.channels) {
+        return;
+    }
+
+    let from_size = vec2<f32>(f32(uniforms.from_width), f32(uniforms.from_height));
+    let to_size = vec2<f32>(f32(uniforms.to_width), f32(uniforms.to_height));
+
+    var value = 0.0;
+
+    switch uniforms.mode {
+        case RESAMPLE_NEAREST: {
+            let p = min(vec2<u32>((vec2<f32>(f32(x), f32(y)) + 0.5) * from_size / to_size), vec2<u32>(uniforms.from_width - 1u, uniforms.from_height - 1u));
+            value = sample(p.x, p.y, channel);
+        }
+        case RESAMPLE_BILINEAR: {
+            // cell centers line up, edges are clamped
+            let p = clamp((vec2<f32>(f32(x), f32(y)) + 0.5) * from_size / to_size - 0.5, vec2<f32>(0.0), from_size - 1.0);
+            let p0 = vec2<u32>(floor(p));
+            let p1 = min(p0 + 1u, vec2<u32>(uniforms.from_width - 1u, uniforms.from_height - 1u));
+            let t = fract(p);
+
+            let top = mix(sample(p0.x, p0.y, channel), sample(p1.x, p0.y, channel), t.x);
+            let bottom = mix(sample(p0.x, p1.y, channel), sample(p1.x, p1.y, channel), t.x);
+            value = mix(top, bottom, t.y);
+        }
+        case RESAMPLE_TILE: {
+            value = sample(x % uniforms.from_width, y % uniforms.from_height, channel);
+        }
+        default: {
+            // keep the middle of the old grid in the middle of the new one, pad with dead cells
+            let sx = i32(x) - (i32(uniforms.to_width) - i32(uniforms.from_width)) / 2;
+            let sy = i32(y) - (i32(uniforms.to_height) - i32(uniforms.from_height)) / 2;
+
+            if (sx >= 0 && sy >= 0 && sx < i32(uniforms.from_width) && sy < i32(uniforms.from_height)) {
+                value = sample(u32(sx), u32(sy), channel);
+            }
+        }
+    }
+
+    to_grid[channel * uniforms.to_width * uniforms.to_height + y * uniforms.to_width + x] = value;
+}
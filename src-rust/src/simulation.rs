@@ -0,0 +1,116 @@
+use anyhow::anyhow;
+
+use crate::{fft_compute::{BoundaryMode, GrowthFunction, UpdateRule}, kernel_description::KernelDescription};
+
+/// One entry of the interaction matrix, convolves the `source` channel and feeds its growth into `target`
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChannelKernel {
+    pub kernel: KernelDescription,
+    pub source: u32,
+    pub target: u32,
+    pub m: f32,
+    pub s: f32,
+    /// scales this kernel's growth before it is summed into the target channel
+    pub weight: f32,
+}
+
+impl Default for ChannelKernel {
+    fn default() -> Self {
+        Self {
+            kernel: KernelDescription::default(),
+            source: 0,
+            target: 0,
+            m: 0.135,
+            s: 0.015,
+            weight: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SimulationConfig {
+    pub channels: u32,
+    /// steps per unit of time T, each step advances by dt = 1 / T
+    pub time_step: f32,
+    pub growth_function: GrowthFunction,
+    #[serde(default)]
+    pub boundary: BoundaryMode,
+    #[serde(default)]
+    pub update_rule: UpdateRule,
+    pub kernels: Vec<ChannelKernel>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            channels: 1,
+            time_step: 50.0,
+            growth_function: GrowthFunction::Gaussian,
+            boundary: BoundaryMode::Torus,
+            update_rule: UpdateRule::Euler,
+            kernels: vec![ChannelKernel::default()],
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.channels == 0 {
+            return Err(anyhow!("simulation needs at least one channel"));
+        }
+
+        if self.kernels.is_empty() {
+            return Err(anyhow!("simulation needs at least one kernel"));
+        }
+
+        if !self.time_step.is_finite() || self.time_step <= 0.0 {
+            return Err(anyhow!("time step has to be a positive number"));
+        }
+
+        for (i, kernel) in self.kernels.iter().enumerate() {
+            if kernel.source >= self.channels || kernel.target >= self.channels {
+                return Err(anyhow!(
+                    "kernel {} connects channel {} to {}, but there are only {} channels",
+                    i, kernel.source, kernel.target, self.channels
+                ));
+            }
+
+            kernel.kernel.validate().map_err(|e| anyhow!("kernel {}: {}", i, e))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn max_radius(&self) -> u32 {
+        self.kernels.iter().map(|k| k.kernel.radius).max().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_degenerate_kernels() {
+        assert!(SimulationConfig::default().validate().is_ok());
+
+        let degenerate = [
+            KernelDescription { radius: 0, ..Default::default() },
+            KernelDescription { radius: 1, ..Default::default() },
+            KernelDescription { shells: vec![0.0], ..Default::default() },
+            KernelDescription { shells: vec![0.0, 0.0, 0.0], ..Default::default() },
+            KernelDescription { shells: vec![], ..Default::default() },
+            KernelDescription { shells: vec![1.0, f32::NAN], ..Default::default() },
+            KernelDescription { width: 0.0, ..Default::default() },
+            KernelDescription { width: -0.5, ..Default::default() },
+        ];
+
+        for kernel in degenerate {
+            let config = SimulationConfig {
+                kernels: vec![ChannelKernel { kernel: kernel.clone(), ..Default::default() }],
+                ..Default::default()
+            };
+            assert!(config.validate().is_err(), "{:?} was accepted", kernel);
+        }
+    }
+}
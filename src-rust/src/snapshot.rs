@@ -0,0 +1,178 @@
+//! Binary snapshot of the world, everything needed to resume a run.
+//!
+//! All integers are little endian.
+//!
+//! | offset | size | field                                                       |
+//! |--------|------|-------------------------------------------------------------|
+//! | 0      | 4    | magic, `LNIA`                                               |
+//! | 4      | 2    | format version, currently 1                                 |
+//! | 6      | 2    | flags, bit 0 set if the body is zlib compressed             |
+//! | 8      | 4    | width                                                       |
+//! | 12     | 4    | height                                                      |
+//! | 16     | 4    | channels                                                    |
+//! | 20     | 8    | step counter                                                |
+//! | 28     | 4    | length `n` of the simulation config                         |
+//! | 32     | n    | `SimulationConfig` as JSON, kernels and growth parameters   |
+//! | 32 + n | ..   | body, channels x height x width f32 cells, plane by plane   |
+
+use anyhow::anyhow;
+
+use crate::simulation::SimulationConfig;
+
+const MAGIC: &[u8; 4] = b"LNIA";
+const VERSION: u16 = 1;
+const FLAG_COMPRESSED: u16 = 1;
+const HEADER_SIZE: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub width: u32,
+    pub height: u32,
+    pub step: u64,
+    pub config: SimulationConfig,
+    /// `config.channels` planes of width x height cells, same layout as the grid buffer
+    pub cells: Vec<f32>,
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self, compress: bool) -> anyhow::Result<Vec<u8>> {
+        let config = serde_json::to_vec(&self.config)?;
+        let cells = self.cells.iter().flat_map(|c| c.to_le_bytes()).collect::<Vec<_>>();
+
+        let body = if compress {
+            miniz_oxide::deflate::compress_to_vec_zlib(&cells, 6)
+        } else {
+            cells
+        };
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + config.len() + body.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(if compress { FLAG_COMPRESSED } else { 0 }).to_le_bytes());
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&self.config.channels.to_le_bytes());
+        bytes.extend_from_slice(&self.step.to_le_bytes());
+        bytes.extend_from_slice(&(config.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&config);
+        bytes.extend_from_slice(&body);
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+            return Err(anyhow!("not a lenia snapshot"));
+        }
+
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+
+        let version = u16_at(4);
+        if version != VERSION {
+            return Err(anyhow!("unsupported snapshot version {}", version));
+        }
+
+        let flags = u16_at(6);
+        let width = u32_at(8);
+        let height = u32_at(12);
+        let channels = u32_at(16);
+        let step = u64::from_le_bytes(bytes[20..28].try_into().unwrap());
+        let config_len = u32_at(28) as usize;
+
+        if width == 0 || height == 0 {
+            return Err(anyhow!("snapshot world is {}x{}, it needs at least one cell", width, height));
+        }
+
+        // usize is 32 bits on wasm, a corrupt length could wrap around
+        let config_end = HEADER_SIZE.checked_add(config_len).ok_or_else(|| anyhow!("snapshot is truncated"))?;
+        let config_bytes = bytes
+            .get(HEADER_SIZE..config_end)
+            .ok_or_else(|| anyhow!("snapshot is truncated"))?;
+        let config: SimulationConfig = serde_json::from_slice(config_bytes)?;
+        config.validate()?;
+
+        if config.channels != channels {
+            return Err(anyhow!("snapshot header has {} channels but its config has {}", channels, config.channels));
+        }
+
+        let body = &bytes[config_end..];
+        let body = if flags & FLAG_COMPRESSED != 0 {
+            miniz_oxide::inflate::decompress_to_vec_zlib(body).map_err(|e| anyhow!("could not decompress snapshot: {}", e))?
+        } else {
+            body.to_vec()
+        };
+
+        let body_size = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|n| n.checked_mul(channels as usize))
+            .and_then(|n| n.checked_mul(4))
+            .ok_or_else(|| anyhow!("snapshot world of {}x{} cells and {} channels is too large", width, height, channels))?;
+        if body.len() != body_size {
+            return Err(anyhow!("snapshot should hold {} cells, found {} bytes", body_size / 4, body.len()));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            step,
+            config,
+            cells: body.chunks_exact(4).map(|c| f32::from_le_bytes(c.try_into().unwrap())).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let config = SimulationConfig { channels: 2, ..Default::default() };
+        let (width, height) = (7, 5);
+
+        Snapshot {
+            width,
+            height,
+            step: 1234,
+            cells: (0..width * height * config.channels).map(|i| i as f32 / 70.0).collect(),
+            config,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let snapshot = snapshot();
+
+        for compress in [false, true] {
+            let bytes = snapshot.to_bytes(compress).unwrap();
+            assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot);
+        }
+    }
+
+    #[test]
+    fn truncated_snapshots_are_rejected() {
+        for compress in [false, true] {
+            let bytes = snapshot().to_bytes(compress).unwrap();
+
+            for len in 0..bytes.len() {
+                assert!(Snapshot::from_bytes(&bytes[..len]).is_err(), "{} of {} bytes were accepted", len, bytes.len());
+            }
+        }
+    }
+
+    #[test]
+    fn empty_worlds_are_rejected() {
+        for (width, height) in [(0, 5), (7, 0), (0, 0)] {
+            let snapshot = Snapshot { width, height, cells: vec![], ..snapshot() };
+            let bytes = snapshot.to_bytes(false).unwrap();
+            assert!(Snapshot::from_bytes(&bytes).is_err(), "{}x{} was accepted", width, height);
+        }
+    }
+
+    #[test]
+    fn config_length_past_the_end_is_rejected() {
+        let mut bytes = snapshot().to_bytes(false).unwrap();
+        bytes[28..32].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Snapshot::from_bytes(&bytes).is_err());
+    }
+}
@@ -1,7 +1,9 @@
+use std::{cell::RefCell, rc::Rc};
+
 use anyhow::anyhow;
 
 use crate::{
-    Parameters, compute::{ComputeState, ComputeUniforms}, fft_compute::{FFTComputeState}, random::{RandomState, RandomUniforms}, render::{RenderState, RenderUniforms}, storage_manager::Storage, uniforms_manager::Uniforms
+    Parameters, animal::Animal, brush::{Brush, BrushState, BrushUniforms, Pattern}, colormap::Colormap, image::ImageFormat, recorder::Recorder, compute::{ComputeState, ComputeUniforms}, fft_compute::{FFTComputeState, FFTState, FFTUniforms}, kernel_description::KernelDescription, render::{FilterMode, RenderMode, RenderState, RenderUniforms}, reduction_manager::{Elements, ReduceOp, Reducer}, resample::{ResampleMode, ResampleState, ResampleUniforms}, simulation::SimulationConfig, snapshot::Snapshot, stats::{Stats, StatsState, StatsUniforms}, tracking::{TrackedObject, Tracker, segment}, storage_manager::Storage, uniforms_manager::Uniforms
 };
 
 pub struct State {
@@ -12,9 +14,23 @@ pub struct State {
     render: RenderState,
     compute: ComputeState,
     fft_compute: FFTComputeState,
-    random: RandomState,
+    brush: BrushState,
+    stats: StatsState,
+    reducer: Reducer,
+    /// shared with the futures of `track`, which update it once the grid has been read
+    tracker: Rc<RefCell<Tracker>>,
+    resample: ResampleState,
     grid: Storage,
+    /// size of the simulation, follows the canvas unless `fixed_world_size` is set
+    world_width: u32,
+    world_height: u32,
+    fixed_world_size: bool,
     encoder: wgpu::CommandEncoder,
+    /// number of simulation steps since the world was last cleared
+    step: u64,
+    /// simulated time over the same steps
+    time: f64,
+    recorder: Option<Recorder>,
 }
 
 impl State {
@@ -37,7 +53,10 @@ impl State {
             })
             .await?;
 
-        let (device, queue) = adapter.request_device(&Default::default()).await?;
+        let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
+            required_limits: FFTState::required_limits(&adapter),
+            ..Default::default()
+        }).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -57,13 +76,11 @@ impl State {
         };
         surface.configure(&device, &config);
 
-        let buffer_size = (width * height * 4) as u64;
-        let grid = Storage::new_empty(&device, "Grid", buffer_size);
+        let simulation = SimulationConfig::default();
+        let channels = simulation.channels;
 
-        let render_uniforms = Uniforms::new(&device, "Render", RenderUniforms {
-            height, width, ..Default::default()
-        });
-        let render = RenderState::new(&device, &grid, render_uniforms, &config);
+        let buffer_size = (width * height * channels * 4) as u64;
+        let grid = Storage::new_empty(&device, "Grid", buffer_size);
 
         let compute_uniforms = Uniforms::new(&device, "Compute", ComputeUniforms {
             height, width, ..Default::default()
@@ -72,12 +89,43 @@ impl State {
 
         let mut encoder = device.create_command_encoder(&Default::default());
 
-        let fft_compute= FFTComputeState::new(&device, &mut encoder, &queue, &grid, width, height);
+        let fft_compute= FFTComputeState::new(&device, &mut encoder, &queue, &grid, width, height, simulation);
 
-        let random_uniforms = Uniforms::new(&device, "Randomness", RandomUniforms {
-            height, width, ..Default::default()
+        let render_uniforms = Uniforms::new(&device, "Render", RenderUniforms {
+            height,
+            width,
+            channels,
+            view_width: width,
+            view_height: height,
+            zoom: 1.0,
+            center_x: width as f32 / 2.0,
+            center_y: height as f32 / 2.0,
+            filter_mode: FilterMode::Nearest as u32,
+            render_mode: RenderMode::State as u32,
+            layer: 0,
+            range_min: 0.0,
+            range_max: 1.0,
+            highlight_out_of_range: 0,
+            fft_width: fft_compute.size().0,
+            fft_height: fft_compute.size().1,
+        });
+        let render = RenderState::new(&device, &grid, fft_compute.buffers(), render_uniforms, config.format);
+
+        let brush_uniforms = Uniforms::new(&device, "Brush", BrushUniforms {
+            height, width, channels, ..Default::default()
+        });
+        let mut brush = BrushState::new(&device, &grid, brush_uniforms);
+        brush.set_brush(&Brush::default());
+
+        let stats_uniforms = Uniforms::new(&device, "Stats", StatsUniforms {
+            height, width, channels, ..Default::default()
         });
-        let random = RandomState::new(&device, &grid, random_uniforms);
+        let stats = StatsState::new(&device, &grid, stats_uniforms);
+
+        let reducer = Reducer::new(&device);
+
+        let resample_uniforms = Uniforms::new(&device, "Resample", ResampleUniforms::default());
+        let resample = ResampleState::new(&device, &grid, resample_uniforms);
 
         Ok(Self {
             surface,
@@ -87,28 +135,228 @@ impl State {
             render,
             compute,
             fft_compute,
-            random,
+            brush,
+            stats,
+            reducer,
+            tracker: Rc::new(RefCell::new(Tracker::new())),
+            resample,
             grid,
+            world_width: width,
+            world_height: height,
+            fixed_world_size: false,
             encoder,
+            step: 0,
+            time: 0.0,
+            recorder: None,
         })
     }
 
     pub fn clear(&mut self) {
         self.encoder.clear_buffer(self.grid.buffer(), 0, None);
+        self.step = 0;
+        self.time = 0.0;
+        self.tracker.borrow_mut().reset();
     }
 
-    pub fn parse_parameters(&mut self, parameters: Parameters) {
-        self.random.uniforms.density = parameters.random_density;
-        self.random.uniforms.size = parameters.random_brush_size;
-        self.random.uniforms.seed = parameters.random_seed;
+    pub fn parse_parameters(&mut self, parameters: Parameters) -> anyhow::Result<()> {
+        self.brush.uniforms.value = parameters.random_density;
+        self.brush.uniforms.size = parameters.random_brush_size as f32;
+        self.brush.uniforms.seed = parameters.random_seed;
 
         self.compute.uniforms.m = parameters.compute_m;
         self.compute.uniforms.s = parameters.compute_s;
         self.compute.uniforms.time_step = parameters.compute_time_step;
+        self.compute.uniforms.growth_function = parameters.compute_growth_function as u32;
+
+        self.set_resample_mode(parameters.world_resample);
+        self.set_filter_mode(parameters.render_filter);
+
+        // the sliders edit the first kernel
+        let mut config = self.fft_compute.config().clone();
+        config.time_step = parameters.compute_time_step;
+        config.growth_function = parameters.compute_growth_function;
+        config.boundary = parameters.compute_boundary;
+        config.update_rule = parameters.compute_update_rule;
+        if let Some(kernel) = config.kernels.first_mut() {
+            kernel.m = parameters.compute_m;
+            kernel.s = parameters.compute_s;
+            kernel.kernel.radius = parameters.compute_radius;
+        }
+
+        let result = self.fft_compute.update(&self.device, &mut self.encoder, &self.queue, &self.grid, config);
+        self.rebind_render();
+        result
+    }
+
+    pub fn simulation_config(&self) -> &SimulationConfig {
+        self.fft_compute.config()
+    }
+
+    pub fn set_kernel(&mut self, index: usize, kernel: KernelDescription) -> anyhow::Result<()> {
+        self.fft_compute.set_kernel(&self.device, &mut self.encoder, &self.queue, &self.grid, index, kernel)?;
+        self.rebind_render();
+        Ok(())
+    }
+
+    /// replaces the channels and kernels, the grid is cleared and the run starts over if the channel count changes
+    pub fn configure(&mut self, config: SimulationConfig) -> anyhow::Result<()> {
+        config.validate()?;
+
+        let width = self.world_width;
+        let height = self.world_height;
+        let channels = config.channels;
+
+        if channels != self.fft_compute.config().channels {
+            let buffer_size = (width * height * channels * 4) as u64;
+            self.grid = Storage::new_empty(&self.device, "Grid", buffer_size);
+
+            self.brush.recreate_bind_groups(&self.device, &self.grid);
+            self.stats.recreate_bind_groups(&self.device, &self.grid);
+
+            self.brush.uniforms.channels = channels;
+            self.stats.uniforms.channels = channels;
+            self.render.uniforms.channels = channels;
+
+            // the new grid starts empty, like after `clear`
+            self.step = 0;
+            self.time = 0.0;
+            self.tracker.borrow_mut().reset();
+        }
+
+        if channels == self.fft_compute.config().channels && config.kernels.len() == self.fft_compute.config().kernels.len() {
+            let result = self.fft_compute.update(&self.device, &mut self.encoder, &self.queue, &self.grid, config);
+            self.rebind_render();
+            return result;
+        }
+
+        self.fft_compute.rebuild(&self.device, &mut self.encoder, &self.queue, &self.grid, width, height, config);
+        self.rebind_render();
+
+        Ok(())
+    }
+
+    /// the render pass reads the grid and the fft buffers, both can be swapped out by the calls above
+    fn rebind_render(&mut self) {
+        (self.render.uniforms.fft_width, self.render.uniforms.fft_height) = self.fft_compute.size();
+        self.render.recreate_bind_groups(&self.device, &self.grid, self.fft_compute.buffers());
+    }
+
+    /// maps a pixel of the canvas to the cell under it, wrapping around outside the world
+    fn canvas_to_world(&self, x: u32, y: u32) -> (u32, u32) {
+        let (wx, wy) = self.render.screen_to_world(x as f32, y as f32);
+
+        (
+            (wx.floor() as i64).rem_euclid(self.world_width as i64) as u32,
+            (wy.floor() as i64).rem_euclid(self.world_height as i64) as u32,
+        )
     }
 
-    pub fn randomize_area(&mut self, x: u32, y: u32) {
-        self.random.run(
+    pub fn screen_to_cell(&self, x: f32, y: f32) -> Option<(u32, u32)> {
+        self.render.screen_to_cell(x, y)
+    }
+
+    pub fn zoom_at(&mut self, factor: f32, x: f32, y: f32) {
+        self.render.zoom_at(factor, x, y);
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.render.pan(dx, dy);
+    }
+
+    pub fn reset_camera(&mut self) {
+        self.render.reset_camera();
+    }
+
+    pub fn set_filter_mode(&mut self, filter_mode: FilterMode) {
+        self.render.uniforms.filter_mode = filter_mode as u32;
+    }
+
+    /// `layer` is the kernel for the potential and the channel otherwise, `range` defaults to one fitting the mode
+    pub fn set_render_mode(&mut self, mode: RenderMode, layer: u32, range: Option<(f32, f32)>) {
+        self.render.set_render_mode(mode, layer, range);
+    }
+
+    /// finds the objects above `threshold` and matches them with the ones found by the last call
+    pub fn track(&mut self, threshold: f32) -> impl Future<Output = anyhow::Result<Vec<TrackedObject>>> + use<> {
+        self.flush();
+
+        let cells = self.grid.read(&self.device, &self.queue);
+
+        let width = self.world_width;
+        let height = self.world_height;
+        let channels = self.fft_compute.config().channels;
+        let step = self.step;
+        let time = self.time;
+        let tracker = self.tracker.clone();
+
+        async move {
+            let cells = cells.await?;
+            let grid: Vec<f32> = bytemuck::pod_collect_to_vec(&cells);
+
+            let objects = segment(&grid, width, height, channels, threshold);
+            Ok(tracker.borrow_mut().update(objects, width, height, step, time).to_vec())
+        }
+    }
+
+    /// smallest and largest value `mode` shows for `layer`, to fit the render range to.
+    /// The fft modes include the padding of their planes.
+    pub fn value_range(&mut self, mode: RenderMode, layer: u32) -> impl Future<Output = anyhow::Result<(f32, f32)>> + use<> {
+        self.flush();
+
+        let config = self.fft_compute.config();
+        let buffers = self.fft_compute.buffers();
+        let (fft_width, fft_height) = self.fft_compute.size();
+        let plane_size = fft_width * fft_height;
+        let channel = layer.min(config.channels - 1);
+
+        // the fields hold growth and change as complex numbers, the potential is real with padded rows whose padding is 0
+        let (input, elements, scale) = match mode {
+            RenderMode::State => (&self.grid, Elements::contiguous(channel * self.world_width * self.world_height, self.world_width * self.world_height), 1.0),
+            RenderMode::Potential => {
+                let kernel = layer.min(config.kernels.len() as u32 - 1);
+                let potential_plane_size = fft_height * FFTUniforms::real_stride(fft_width);
+                (&buffers.potential, Elements::contiguous(kernel * potential_plane_size, potential_plane_size), 1.0 / plane_size as f32)
+            }
+            RenderMode::Growth => (&buffers.fields, Elements { offset: channel * plane_size * 2, stride: 2, len: plane_size }, 1.0),
+            RenderMode::Delta => (&buffers.fields, Elements { offset: channel * plane_size * 2 + 1, stride: 2, len: plane_size }, 1.0),
+        };
+
+        let values = self.reducer.read(&self.device, &self.queue, input, elements, &[ReduceOp::Min, ReduceOp::Max]);
+
+        async move {
+            let values = values.await?;
+            Ok((values[0] * scale, values[1] * scale))
+        }
+    }
+
+    pub fn set_out_of_range_highlight(&mut self, highlight: bool) {
+        self.render.uniforms.highlight_out_of_range = highlight as u32;
+    }
+
+    pub fn set_colormap(&mut self, colormap: Colormap) -> anyhow::Result<()> {
+        colormap.validate()?;
+        self.render.set_colormap(&self.queue, &colormap);
+        Ok(())
+    }
+
+    /// the size and value set here are overwritten by the next `parse_parameters`
+    pub fn set_brush(&mut self, brush: Brush) -> anyhow::Result<()> {
+        brush.validate()?;
+        self.brush.set_brush(&brush);
+        Ok(())
+    }
+
+    pub fn set_pattern(&mut self, pattern: Pattern) -> anyhow::Result<()> {
+        self.brush.set_pattern(&self.device, &self.grid, &pattern)
+    }
+
+    /// `x` and `y` are in canvas pixels
+    pub fn paint_area(&mut self, x: u32, y: u32) {
+        let Some((x, y)) = self.screen_to_cell(x as f32, y as f32) else {
+            return;
+        };
+
+        self.brush.paint(
             &mut self.encoder,
             &self.queue,
             x, 
@@ -116,8 +364,165 @@ impl State {
         );
     }
 
+    /// `x` and `y` are in canvas pixels
+    pub fn paste_pattern(&mut self, x: u32, y: u32) {
+        let Some((x, y)) = self.screen_to_cell(x as f32, y as f32) else {
+            return;
+        };
+
+        self.brush.paste(&mut self.encoder, &self.queue, x, y);
+    }
+
     pub fn step(&mut self) {
         self.fft_compute.run(&mut self.encoder, &self.queue);
+        self.step += 1;
+        self.time += 1.0 / self.fft_compute.config().time_step as f64;
+    }
+
+    pub fn step_count(&self) -> u64 {
+        self.step
+    }
+
+    /// simulated time, each step advances it by 1 / time_step of the config it ran with
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// measures the grid after every step recorded so far, cells above `threshold` count as alive
+    pub fn stats(&mut self, threshold: f32) -> impl Future<Output = anyhow::Result<Stats>> + use<> {
+        self.flush();
+
+        let stats = self.stats.read(&self.device, &self.queue, threshold);
+        let step = self.step;
+        let time = self.time;
+
+        async move {
+            Ok(Stats { step, time, ..stats.await? })
+        }
+    }
+
+    /// submits everything recorded so far
+    fn flush(&mut self) {
+        let encoder = std::mem::replace(
+            &mut self.encoder,
+            self.device.create_command_encoder(&Default::default()),
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// what the canvas shows, drawn at width x height (the canvas size by default) and encoded as `format`
+    pub fn capture_frame(&mut self, format: ImageFormat, size: Option<(u32, u32)>) -> impl Future<Output = anyhow::Result<Vec<u8>>> + use<> {
+        self.flush();
+
+        let (width, height) = size.unwrap_or((self.config.width, self.config.height));
+        let rgba = self.render.capture(&self.device, &self.queue, width, height);
+
+        async move {
+            Ok(format.encode(width, height, &rgba.await?))
+        }
+    }
+
+    pub fn start_recording(&mut self, every: u64, format: ImageFormat) {
+        self.recorder = Some(Recorder::new(every, format));
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    /// the name and contents of the next frame of the recording, if the current step is part of it
+    pub fn record_frame(&mut self) -> Option<(String, impl Future<Output = anyhow::Result<Vec<u8>>> + use<>)> {
+        let recorder = self.recorder.as_mut()?;
+        let format = recorder.format;
+        let name = recorder.frame_for_step(self.step)?;
+
+        Some((name, self.capture_frame(format, None)))
+    }
+
+    /// reads the grid back and serializes it along with the simulation config, see `snapshot.rs` for the format
+    pub fn export_state(&mut self, compress: bool) -> impl Future<Output = anyhow::Result<Vec<u8>>> + use<> {
+        self.flush();
+
+        let cells = self.grid.read(&self.device, &self.queue);
+
+        let width = self.world_width;
+        let height = self.world_height;
+        let step = self.step;
+        let config = self.fft_compute.config().clone();
+
+        async move {
+            let cells = cells.await?;
+
+            Snapshot {
+                width,
+                height,
+                step,
+                config,
+                cells: bytemuck::pod_collect_to_vec(&cells),
+            }.to_bytes(compress)
+        }
+    }
+
+    /// applies the animal's parameters and stamps it centered on the canvas pixel (x, y), wrapping around the edges.
+    /// The grid is cleared if the animal has a different number of channels.
+    pub fn load_animal(&mut self, animal: Animal, x: u32, y: u32) -> anyhow::Result<()> {
+        let (x, y) = self.canvas_to_world(x, y);
+
+        let mut config = animal.config.clone();
+        config.boundary = self.fft_compute.config().boundary;
+        self.configure(config)?;
+
+        // commands already recorded (a clear for example) have to land before the stamp
+        self.flush();
+
+        let width = self.world_width;
+        let height = self.world_height;
+
+        for (channel, grid_x, grid_y, cells) in animal.stamp_runs(width, height, x, y) {
+            let offset = ((channel * height + grid_y) * width + grid_x) as u64 * 4;
+            self.queue.write_buffer(self.grid.buffer(), offset, bytemuck::cast_slice(cells));
+        }
+
+        Ok(())
+    }
+
+    /// reads the grid back and writes everything alive as an animal in the catalogue format
+    pub fn export_animal(&mut self, code: String, name: String) -> impl Future<Output = anyhow::Result<String>> + use<> {
+        self.flush();
+
+        let cells = self.grid.read(&self.device, &self.queue);
+
+        let width = self.world_width;
+        let height = self.world_height;
+        let config = self.fft_compute.config().clone();
+
+        async move {
+            let cells = cells.await?;
+            let grid: Vec<f32> = bytemuck::pod_collect_to_vec(&cells);
+
+            Animal::from_grid(code, name, config, width, height, &grid)?.to_json()
+        }
+    }
+
+    /// restores a snapshot made by `export_state`, the world size is fixed to the snapshot's
+    pub fn import_state(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        let snapshot = Snapshot::from_bytes(bytes)?;
+
+        self.configure(snapshot.config)?;
+
+        // the world keeps the size of the snapshot, whatever the canvas does
+        self.set_world_size(Some((snapshot.width, snapshot.height)));
+
+        // the resample recorded above must not run after the write
+        self.flush();
+        self.queue.write_buffer(self.grid.buffer(), 0, bytemuck::cast_slice(&snapshot.cells));
+        self.step = snapshot.step;
+        self.tracker.borrow_mut().reset();
+        // snapshots don't keep the time, this is exact unless time_step changed during the run
+        self.time = snapshot.step as f64 / self.fft_compute.config().time_step as f64;
+
+        Ok(())
     }
 
     pub fn render(&mut self) {
@@ -138,8 +543,9 @@ impl State {
         output.present();
     }
 
+    /// resizes the canvas, the world follows it unless its size is fixed
     pub fn resize(&mut self, width: u32, height: u32) {
-        if width <= 0 || height <= 0 {
+        if width == 0 || height == 0 {
             return;
         }
 
@@ -150,23 +556,67 @@ impl State {
         self.config.width = width;
         self.config.height = height;
         self.surface.configure(&self.device, &self.config);
-        
-        let buffer_size = (width * height * 4) as u64;
-        self.grid = Storage::new_empty(&self.device, "Grid", buffer_size);
 
-        self.random.recreate_bind_groups(&self.device, &self.grid);
-        self.render.recreate_bind_groups(&self.device, &self.grid);
+        self.render.uniforms.view_width = width;
+        self.render.uniforms.view_height = height;
+
+        if !self.fixed_world_size {
+            self.resize_world(width, height);
+        }
+
+        self.render()
+    }
+
+    /// `Some` fixes the world at that size, `None` makes it follow the canvas again
+    pub fn set_world_size(&mut self, size: Option<(u32, u32)>) {
+        self.fixed_world_size = size.is_some();
+
+        let (width, height) = size.unwrap_or((self.config.width, self.config.height));
+        self.resize_world(width, height);
+    }
+
+    pub fn set_resample_mode(&mut self, mode: ResampleMode) {
+        self.resample.uniforms.mode = mode as u32;
+    }
+
+    /// moves the world into a grid of the new size with the current resample mode
+    fn resize_world(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width == self.world_width && height == self.world_height) {
+            return;
+        }
+
+        let channels = self.fft_compute.config().channels;
+        let buffer_size = (width * height * channels * 4) as u64;
+        let grid = Storage::new_empty(&self.device, "Grid", buffer_size);
+
+        self.resample.uniforms.from_width = self.world_width;
+        self.resample.uniforms.from_height = self.world_height;
+        self.resample.uniforms.to_width = width;
+        self.resample.uniforms.to_height = height;
+        self.resample.uniforms.channels = channels;
+        self.resample.run(&self.device, &mut self.encoder, &self.queue, &self.grid, &grid);
+
+        self.grid = grid;
+        self.world_width = width;
+        self.world_height = height;
+
+        self.brush.recreate_bind_groups(&self.device, &self.grid);
+        self.stats.recreate_bind_groups(&self.device, &self.grid);
         // self.compute.recreate_bind_groups(&self.device, &self.grid);
 
-        self.random.uniforms.width = width;
-        self.random.uniforms.height = height;
+        self.brush.uniforms.width = width;
+        self.brush.uniforms.height = height;
+        self.stats.uniforms.width = width;
+        self.stats.uniforms.height = height;
+        // positions don't carry over to the new size
+        self.tracker.borrow_mut().reset();
         self.render.uniforms.width = width;
         self.render.uniforms.height = height;
+        self.render.reset_camera();
         // self.compute.uniforms.width = width;
         // self.compute.uniforms.height = height;
 
         self.fft_compute.handle_resize(&self.device, &mut self.encoder, &self.queue, &self.grid, height, width);
-
-        self.render()
+        self.rebind_render();
     }
 }
@@ -0,0 +1,230 @@
+use std::f64::consts::TAU;
+
+use crate::{storage_manager::{Storage, read_mapped}, uniforms_manager::Uniforms};
+
+/// workgroups in one reduction, each thread strides over the grid so this caps the partials read back
+const WORKGROUPS: u32 = 256;
+
+/// Statistics of the grid, values are summed over every channel
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Stats {
+    pub step: u64,
+    /// simulated time, the sum of every step's 1 / time_step
+    pub time: f64,
+    pub mass: f64,
+    /// mean and variance over every cell of every channel
+    pub mean: f64,
+    pub variance: f64,
+    /// cells above the threshold, counted once per channel
+    pub live: u32,
+    /// circular mean of the mass in cells, `None` when the grid is empty
+    pub center: Option<[f64; 2]>,
+    /// smallest box holding every live cell, it doesn't account for creatures crossing the edges
+    pub bounds: Option<Bounds>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Bounds {
+    pub min_x: u32,
+    pub min_y: u32,
+    pub max_x: u32,
+    pub max_y: u32,
+}
+
+/// matches `Partial` in stats.wgsl
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Partial {
+    mass: f32,
+    sum_squares: f32,
+    live: u32,
+    cos_x: f32,
+    sin_x: f32,
+    cos_y: f32,
+    sin_y: f32,
+    min_x: u32,
+    min_y: u32,
+    max_x: u32,
+    max_y: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default, encase::ShaderType)]
+pub struct StatsUniforms {
+    pub height: u32,
+    pub width: u32,
+    pub channels: u32,
+    /// cells above it count as alive
+    pub threshold: f32,
+}
+
+pub struct StatsState {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    partials: Storage,
+    pub uniforms: Uniforms<StatsUniforms>,
+}
+
+impl StatsState {
+    fn create_bind_group(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        grid: &Storage,
+        partials: &Storage,
+        uniforms: &Uniforms<StatsUniforms>
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Stats Bind Group"),
+            layout: bind_group_layout,
+            entries: &[
+                uniforms.bind_group_entry(0),
+                grid.bind_group_entry(1),
+                partials.bind_group_entry(2),
+            ]
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        grid: &Storage,
+        uniforms: Uniforms<StatsUniforms>,
+    ) -> Self {
+        let partials = Storage::new_empty(device, "Stats Partials", (WORKGROUPS as usize * size_of::<Partial>()) as u64);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Stats Bind Group Layout"),
+            entries: &[
+                uniforms.layout_entry(0, wgpu::ShaderStages::COMPUTE),
+                grid.layout_entry(1, wgpu::ShaderStages::COMPUTE, true),
+                partials.layout_entry(2, wgpu::ShaderStages::COMPUTE, false),
+            ],
+        });
+
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, grid, &partials, &uniforms);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("stats.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Stats Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Stats Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: None,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            partials,
+            uniforms,
+        }
+    }
+
+    pub fn recreate_bind_groups(
+        &mut self,
+        device: &wgpu::Device,
+        grid: &Storage,
+    ) {
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, grid, &self.partials, &self.uniforms)
+    }
+
+    /// reduces the grid into one partial per workgroup and copies them into `staging`
+    fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        staging: &wgpu::Buffer,
+        workgroups: u32,
+    ) {
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Stats Compute Pass"),
+                timestamp_writes: None,
+            });
+
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+
+            pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+
+        encoder.copy_buffer_to_buffer(self.partials.buffer(), 0, staging, 0, self.partials.buffer().size());
+    }
+
+    /// measures the grid once everything submitted before has run.
+    /// The step and time of the result are left at 0 for the caller to fill in.
+    pub fn read(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        threshold: f32,
+    ) -> impl Future<Output = anyhow::Result<Stats>> + use<> {
+        self.uniforms.threshold = threshold;
+        self.uniforms.write(queue);
+
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Stats Readback Buffer"),
+            size: self.partials.buffer().size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let workgroups = (self.uniforms.width * self.uniforms.height).div_ceil(256).clamp(1, WORKGROUPS);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Stats Encoder") });
+        self.record(&mut encoder, &staging, workgroups);
+        queue.submit(Some(encoder.finish()));
+
+        let width = self.uniforms.width;
+        let height = self.uniforms.height;
+        let values = (width * height * self.uniforms.channels) as f64;
+
+        let bytes = read_mapped(device, staging);
+
+        async move {
+            let bytes = bytes.await?;
+            let partials: Vec<Partial> = bytemuck::pod_collect_to_vec(&bytes);
+
+            let mut stats = Stats::default();
+            let (mut sum_squares, mut cos_x, mut sin_x, mut cos_y, mut sin_y) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            let mut bounds: Option<Bounds> = None;
+
+            for partial in &partials[..workgroups as usize] {
+                stats.mass += partial.mass as f64;
+                sum_squares += partial.sum_squares as f64;
+                stats.live += partial.live;
+                cos_x += partial.cos_x as f64;
+                sin_x += partial.sin_x as f64;
+                cos_y += partial.cos_y as f64;
+                sin_y += partial.sin_y as f64;
+
+                // workgroups without live cells keep the min above the max
+                if partial.min_x <= partial.max_x {
+                    let b = bounds.get_or_insert(Bounds { min_x: u32::MAX, min_y: u32::MAX, max_x: 0, max_y: 0 });
+                    b.min_x = b.min_x.min(partial.min_x);
+                    b.min_y = b.min_y.min(partial.min_y);
+                    b.max_x = b.max_x.max(partial.max_x);
+                    b.max_y = b.max_y.max(partial.max_y);
+                }
+            }
+
+            stats.mean = stats.mass / values;
+            stats.variance = (sum_squares / values - stats.mean * stats.mean).max(0.0);
+            stats.bounds = bounds;
+
+            if stats.mass > 0.0 {
+                let center = |sin: f64, cos: f64, size: u32| (sin.atan2(cos) / TAU).rem_euclid(1.0) * size as f64;
+                stats.center = Some([center(sin_x, cos_x, width), center(sin_y, cos_y, height)]);
+            }
+
+            Ok(stats)
+        }
+    }
+}
@@ -0,0 +1,126 @@
+struct StatsUniforms {
+    height: u32,
+    width: u32,
+    channels: u32,
+    threshold: f32,
+}
+
+// sums over the cells handled by one workgroup, finished on the cpu
+struct Partial {
+    mass: f32,
+    sum_squares: f32,
+    live: u32,
+    // the center of mass is a circular mean so it survives wrapping around the torus
+    cos_x: f32,
+    sin_x: f32,
+    cos_y: f32,
+    sin_y: f32,
+    min_x: u32,
+    min_y: u32,
+    max_x: u32,
+    max_y: u32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: StatsUniforms;
+@group(0) @binding(1) var<storage, read> grid: array<f32>;
+@group(0) @binding(2) var<storage, read_write> partials: array<Partial>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+const TAU: f32 = 6.283185307179586;
+
+var<workgroup> mass: array<f32, WORKGROUP_SIZE>;
+var<workgroup> sum_squares: array<f32, WORKGROUP_SIZE>;
+var<workgroup> live: array<u32, WORKGROUP_SIZE>;
+var<workgroup> cos_x: array<f32, WORKGROUP_SIZE>;
+var<workgroup> sin_x: array<f32, WORKGROUP_SIZE>;
+var<workgroup> cos_y: array<f32, WORKGROUP_SIZE>;
+var<workgroup> sin_y: array<f32, WORKGROUP_SIZE>;
+var<workgroup> min_x: array<u32, WORKGROUP_SIZE>;
+var<workgroup> min_y: array<u32, WORKGROUP_SIZE>;
+var<workgroup> max_x: array<u32, WORKGROUP_SIZE>;
+var<workgroup> max_y: array<u32, WORKGROUP_SIZE>;
+
+@compute
+@workgroup_size(WORKGROUP_SIZE)
+fn reduce(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    let plane_size = uniforms.width * uniforms.height;
+
+    var p = Partial(0.0, 0.0, 0u, 0.0, 0.0, 0.0, 0.0, 0xffffffffu, 0xffffffffu, 0u, 0u);
+
+    // every thread walks the grid with a stride of the whole dispatch
+    for (var i = workgroup.x * WORKGROUP_SIZE + local; i < plane_size; i += workgroups.x * WORKGROUP_SIZE) {
+        let x = i % uniforms.width;
+        let y = i / uniforms.width;
+
+        var total = 0.0;
+        var alive = false;
+        for (var channel = 0u; channel < uniforms.channels; channel++) {
+            let value = grid[channel * plane_size + i];
+            total += value;
+            p.sum_squares += value * value;
+            if (value > uniforms.threshold) {
+                p.live += 1u;
+                alive = true;
+            }
+        }
+
+        p.mass += total;
+
+        let angle = vec2<f32>(f32(x) / f32(uniforms.width), f32(y) / f32(uniforms.height)) * TAU;
+        p.cos_x += total * cos(angle.x);
+        p.sin_x += total * sin(angle.x);
+        p.cos_y += total * cos(angle.y);
+        p.sin_y += total * sin(angle.y);
+
+        if (alive) {
+            p.min_x = min(p.min_x, x);
+            p.min_y = min(p.min_y, y);
+            p.max_x = max(p.max_x, x);
+            p.max_y = max(p.max_y, y);
+        }
+    }
+
+    mass[local] = p.mass;
+    sum_squares[local] = p.sum_squares;
+    live[local] = p.live;
+    cos_x[local] = p.cos_x;
+    sin_x[local] = p.sin_x;
+    cos_y[local] = p.cos_y;
+    sin_y[local] = p.sin_y;
+    min_x[local] = p.min_x;
+    min_y[local] = p.min_y;
+    max_x[local] = p.max_x;
+    max_y[local] = p.max_y;
+
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if (local < stride) {
+            let other = local + stride;
+            mass[local] += mass[other];
+            sum_squares[local] += sum_squares[other];
+            live[local] += live[other];
+            cos_x[local] += cos_x[other];
+            sin_x[local] += sin_x[other];
+            cos_y[local] += cos_y[other];
+            sin_y[local] += sin_y[other];
+            min_x[local] = min(min_x[local], min_x[other]);
+            min_y[local] = min(min_y[local], min_y[other]);
+            max_x[local] = max(max_x[local], max_x[other]);
+            max_y[local] = max(max_y[local], max_y[other]);
+        }
+        workgroupBarrier();
+    }
+
+    if (local == 0u) {
+        partials[workgroup.x] = Partial(
+            mass[0], sum_squares[0], live[0],
+            cos_x[0], sin_x[0], cos_y[0], sin_y[0],
+            min_x[0], min_y[0], max_x[0], max_y[0],
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::{future::Future, pin::Pin, sync::{Arc, Mutex}, task::{Context, Poll, Waker}};
+
 use wgpu::util::DeviceExt;
 
 pub struct Storage {
@@ -12,7 +14,7 @@ impl Storage {
     ) -> Self {
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("{} Buffer", label)),
-            contents: bytemuck::cast_slice(&data),
+            contents: bytemuck::cast_slice(data),
             usage: wgpu::BufferUsages::STORAGE 
                 | wgpu::BufferUsages::COPY_SRC
                 | wgpu::BufferUsages::COPY_DST,
@@ -68,4 +70,89 @@ impl Storage {
             resource: self.buffer.as_entire_binding(),
         }
     }
-}
\ No newline at end of file
+
+    /// copies the buffer into a staging buffer and resolves with its contents once it has been mapped.
+    /// Commands recorded in encoders that haven't been submitted yet are not included.
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> impl Future<Output = anyhow::Result<Vec<u8>>> + use<> {
+        let size = self.buffer.size();
+
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback Encoder") });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &staging, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        read_mapped(device, staging)
+    }
+}
+
+/// maps a `MAP_READ` buffer once the gpu is done with it and resolves with its contents
+pub fn read_mapped(
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    device: &wgpu::Device,
+    staging: wgpu::Buffer,
+) -> impl Future<Output = anyhow::Result<Vec<u8>>> + use<> {
+    let mapped = MapFuture::default();
+    let shared = mapped.shared.clone();
+
+    staging.map_async(wgpu::MapMode::Read, .., move |result| {
+        let mut shared = shared.lock().unwrap();
+        shared.result = Some(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    });
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let device = device.clone();
+
+    async move {
+        // the browser maps the buffer on its own, native backends only do it while the device is polled
+        #[cfg(not(target_arch = "wasm32"))]
+        device.poll(wgpu::PollType::wait_indefinitely())?;
+
+        mapped.await?;
+
+        let data = staging.get_mapped_range(..).to_vec();
+        staging.unmap();
+
+        Ok(data)
+    }
+}
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+/// resolves when the `map_async` callback fires
+#[derive(Default)]
+struct MapFuture {
+    shared: Arc<Mutex<MapState>>,
+}
+
+impl Future for MapFuture {
+    type Output = Result<(), wgpu::BufferAsyncError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap();
+
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
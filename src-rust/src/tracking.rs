@@ -0,0 +1,275 @@
+//! Finds the creatures of a world and follows them from one reading of the grid to the next.
+//!
+//! Objects are the 8-connected groups of cells whose value, summed over the channels, is above a threshold.
+//! The world is a torus, so an object crossing an edge stays in one piece and its center is where you would expect it.
+
+use std::f64::consts::PI;
+
+/// One connected group of live cells
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Object {
+    pub mass: f64,
+    /// number of live cells
+    pub cells: u32,
+    /// center of mass in cells
+    pub center: [f64; 2],
+    /// angle of the longest axis in radians from the x axis, in (-pi/2, pi/2]
+    pub orientation: f64,
+    /// square root of the variance of the mass along the longest and the shortest axis
+    pub extent: [f64; 2],
+}
+
+impl Object {
+    /// radius of a disk with as many cells
+    fn radius(&self) -> f64 {
+        (self.cells as f64 / PI).sqrt()
+    }
+}
+
+/// An object followed across readings
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TrackedObject {
+    /// stays the same for as long as the object is found again
+    pub id: u32,
+    /// step the object was first seen at
+    pub since: u64,
+    pub object: Object,
+    /// cells per unit of simulated time, 0 on the first reading
+    pub velocity: [f64; 2],
+}
+
+/// labels the objects of `channels` planes of width x height cells
+pub fn segment(grid: &[f32], width: u32, height: u32, channels: u32, threshold: f32) -> Vec<Object> {
+    let (width, height) = (width as usize, height as usize);
+    let plane_size = width * height;
+
+    let mut values = vec![0f32; plane_size];
+    for plane in grid.chunks_exact(plane_size).take(channels as usize) {
+        for (value, cell) in values.iter_mut().zip(plane) {
+            *value += cell;
+        }
+    }
+
+    let mut visited = vec![false; plane_size];
+    let mut stack = Vec::new();
+    let mut objects = Vec::new();
+
+    for start in 0..plane_size {
+        if visited[start] || values[start] <= threshold {
+            continue;
+        }
+
+        visited[start] = true;
+        // cells carry their position unwrapped from the start, which keeps objects on an edge in one piece
+        stack.push((start, (start % width) as i64, (start / width) as i64));
+
+        let mut cells = 0u32;
+        let (mut mass, mut sum_x, mut sum_y, mut sum_xx, mut sum_yy, mut sum_xy) = (0f64, 0f64, 0f64, 0f64, 0f64, 0f64);
+
+        while let Some((index, x, y)) = stack.pop() {
+            let value = values[index] as f64;
+            let (fx, fy) = (x as f64, y as f64);
+
+            cells += 1;
+            mass += value;
+            sum_x += value * fx;
+            sum_y += value * fy;
+            sum_xx += value * fx * fx;
+            sum_yy += value * fy * fy;
+            sum_xy += value * fx * fy;
+
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let nx = (x + dx).rem_euclid(width as i64) as usize;
+                    let ny = (y + dy).rem_euclid(height as i64) as usize;
+                    let neighbour = ny * width + nx;
+
+                    if !visited[neighbour] && values[neighbour] > threshold {
+                        visited[neighbour] = true;
+                        stack.push((neighbour, x + dx, y + dy));
+                    }
+                }
+            }
+        }
+
+        let center_x = sum_x / mass;
+        let center_y = sum_y / mass;
+
+        // second moments about the center give the axes of the object
+        let xx = sum_xx / mass - center_x * center_x;
+        let yy = sum_yy / mass - center_y * center_y;
+        let xy = sum_xy / mass - center_x * center_y;
+
+        let spread = (((xx - yy) / 2.0).powi(2) + xy * xy).sqrt();
+        let major = ((xx + yy) / 2.0 + spread).max(0.0);
+        let minor = ((xx + yy) / 2.0 - spread).max(0.0);
+
+        objects.push(Object {
+            mass,
+            cells,
+            center: [center_x.rem_euclid(width as f64), center_y.rem_euclid(height as f64)],
+            orientation: 0.5 * (2.0 * xy).atan2(xx - yy),
+            extent: [major.sqrt(), minor.sqrt()],
+        });
+    }
+
+    objects
+}
+
+/// Gives the objects of successive readings stable ids.
+/// An object is the same as one of the last reading when its center moved less than the radius of either,
+/// so readings have to be close enough for creatures not to outrun themselves.
+#[derive(Clone, Debug, Default)]
+pub struct Tracker {
+    objects: Vec<TrackedObject>,
+    next_id: u32,
+    /// simulated time of the last reading
+    time: f64,
+}
+
+impl Tracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// forgets every object, the next ids start over
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn objects(&self) -> &[TrackedObject] {
+        &self.objects
+    }
+
+    /// matches `objects` with the last reading, objects that were not found again are dropped
+    pub fn update(&mut self, objects: Vec<Object>, width: u32, height: u32, step: u64, time: f64) -> &[TrackedObject] {
+        let offset = |from: f64, to: f64, size: u32| {
+            let size = size as f64;
+            (to - from + size / 2.0).rem_euclid(size) - size / 2.0
+        };
+        let displacement = |from: &Object, to: &Object| [
+            offset(from.center[0], to.center[0], width),
+            offset(from.center[1], to.center[1], height),
+        ];
+
+        // closest pairs are matched first
+        let mut pairs = Vec::new();
+        for (old_index, old) in self.objects.iter().enumerate() {
+            for (new_index, new) in objects.iter().enumerate() {
+                let [dx, dy] = displacement(&old.object, new);
+                let distance = dx.hypot(dy);
+
+                if distance <= old.object.radius().max(new.radius()) {
+                    pairs.push((distance, old_index, new_index));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut matches = vec![None; objects.len()];
+        let mut taken = vec![false; self.objects.len()];
+        for (_, old_index, new_index) in pairs {
+            if !taken[old_index] && matches[new_index].is_none() {
+                taken[old_index] = true;
+                matches[new_index] = Some(old_index);
+            }
+        }
+
+        let elapsed = time - self.time;
+
+        let tracked = objects.into_iter().zip(matches).map(|(object, found)| match found {
+            Some(old_index) => {
+                let old = &self.objects[old_index];
+                let [dx, dy] = displacement(&old.object, &object);
+
+                TrackedObject {
+                    id: old.id,
+                    since: old.since,
+                    object,
+                    velocity: if elapsed > 0.0 { [dx / elapsed, dy / elapsed] } else { old.velocity },
+                }
+            }
+            None => {
+                self.next_id += 1;
+
+                TrackedObject {
+                    id: self.next_id,
+                    since: step,
+                    object,
+                    velocity: [0.0, 0.0],
+                }
+            }
+        }).collect();
+
+        self.objects = tracked;
+        self.time = time;
+
+        &self.objects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a square of `side` cells centered on (x, y), wrapping around the edges, split evenly over two channels
+    fn square(grid: &mut [f32], width: u32, height: u32, x: i64, y: i64, side: i64) {
+        for dy in -side / 2..=side / 2 {
+            for dx in -side / 2..=side / 2 {
+                let cx = (x + dx).rem_euclid(width as i64) as u32;
+                let cy = (y + dy).rem_euclid(height as i64) as u32;
+                for channel in 0..2 {
+                    grid[((channel * height + cy) * width + cx) as usize] = 0.25;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn objects_across_the_edges_stay_whole() {
+        let (width, height) = (20, 16);
+
+        // one square across the corner, one across the left and right edges and one in the middle
+        let mut grid = vec![0f32; 2 * 20 * 16];
+        square(&mut grid, width, height, 0, 0, 5);
+        square(&mut grid, width, height, 19, 8, 3);
+        square(&mut grid, width, height, 10, 4, 3);
+
+        let mut objects = segment(&grid, width, height, 2, 0.1);
+        objects.sort_by(|a, b| b.cells.cmp(&a.cells).then(a.center[0].total_cmp(&b.center[0])));
+
+        assert_eq!(objects.len(), 3, "{:?}", objects);
+        assert_eq!(objects.iter().map(|o| o.cells).collect::<Vec<_>>(), [25, 9, 9]);
+
+        let centers = objects.iter().map(|o| o.center).collect::<Vec<_>>();
+        for (center, expected) in centers.iter().zip([[0.0, 0.0], [10.0, 4.0], [19.0, 8.0]]) {
+            assert!((center[0] - expected[0]).abs() < 1e-9 && (center[1] - expected[1]).abs() < 1e-9, "{:?}", centers);
+        }
+
+        assert!((objects[0].mass - 12.5).abs() < 1e-9);
+        // a square has no longest axis
+        assert!((objects[0].extent[0] - objects[0].extent[1]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tracker_keeps_ids_across_the_edge() {
+        let (width, height) = (20, 16);
+        let mut tracker = Tracker::new();
+
+        let mut ids = vec![];
+        for (step, x) in [17, 19, 1, 3].into_iter().enumerate() {
+            let mut grid = vec![0f32; 2 * 20 * 16];
+            square(&mut grid, width, height, x, 8, 5);
+
+            let tracked = tracker.update(segment(&grid, width, height, 2, 0.1), width, height, step as u64, step as f64);
+            assert_eq!(tracked.len(), 1);
+            ids.push(tracked[0].id);
+
+            if step > 0 {
+                assert!((tracked[0].velocity[0] - 2.0).abs() < 1e-9, "{:?}", tracked[0]);
+            }
+        }
+
+        assert!(ids.iter().all(|id| *id == ids[0]), "{:?}", ids);
+    }
+}